@@ -0,0 +1,34 @@
+// Query generation and timing shared by the benches, pulled in with `mod common;`
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+pub const QUERIES: usize = 1_000_000;
+pub const ROUNDS: u32 = 10;
+
+// xorshift so query order defeats the hardware prefetcher without pulling in a rng crate
+pub fn queries(keys: u64) -> Vec<u64> {
+    let mut state = 0x2545_F491_4F6C_DD1D_u64;
+
+    (0..QUERIES)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % keys
+        })
+        .collect()
+}
+
+// best of ROUNDS runs
+pub fn time<F: FnMut() -> u64>(mut f: F) -> Duration {
+    let mut best = Duration::MAX;
+
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        black_box(f());
+        best = best.min(start.elapsed());
+    }
+
+    best
+}
@@ -0,0 +1,45 @@
+// get_many against a loop of get, run with `cargo bench --bench get_many`
+
+use std::time::Duration;
+
+use frozen_map::map::{FrozenMap, UnsafeFrozenMap};
+
+mod common;
+
+use common::{QUERIES, queries, time};
+
+const KEYS: u64 = 4_000_000;
+
+// callers read what they look up, so the value slot is touched on both sides
+fn sum(found: Vec<Option<&[u64; 4]>>) -> u64 {
+    found.into_iter().flatten().map(|v| v[0]).sum()
+}
+
+fn report(name: &str, looped: Duration, batched: Duration) {
+    let per = |d: Duration| d.as_nanos() as f64 / QUERIES as f64;
+
+    println!(
+        "{name:<16} get {:>6.1} ns/key   get_many {:>6.1} ns/key   speedup {:.2}x",
+        per(looped),
+        per(batched),
+        looped.as_secs_f64() / batched.as_secs_f64()
+    );
+}
+
+fn main() {
+    let keys: Vec<u64> = (0..KEYS).collect();
+    let queries = queries(KEYS);
+
+    let verified: FrozenMap<u64, [u64; 4]> = FrozenMap::from_keys_with(keys.clone(), |k| [*k; 4]);
+
+    let looped = time(|| sum(queries.iter().map(|k| verified.get(k)).collect()));
+    let batched = time(|| sum(verified.get_many(&queries)));
+    report("FrozenMap", looped, batched);
+
+    let unverified: UnsafeFrozenMap<u64, [u64; 4]> =
+        UnsafeFrozenMap::from_keys_with(keys, |k| [*k; 4]);
+
+    let looped = time(|| sum(queries.iter().map(|k| unverified.get(k)).collect()));
+    let batched = time(|| sum(unverified.get_many(&queries)));
+    report("UnsafeFrozenMap", looped, batched);
+}
@@ -0,0 +1,60 @@
+// FrozenMap (SoA) against FrozenMapAoS lookups across key and value sizes,
+// run with `cargo bench --bench layout`
+
+use std::hash::Hash;
+use std::time::Duration;
+
+use frozen_map::map::{FrozenMap, FrozenMapAoS};
+
+mod common;
+
+use common::{QUERIES, queries, time};
+
+const KEYS: u64 = 1_000_000;
+
+// callers read what they look up, so the value slot is touched on both sides
+fn sum<'a, V, I>(found: I) -> u64
+where
+    V: AsRef<[u64]> + 'a,
+    I: Iterator<Item = Option<&'a V>>,
+{
+    found.flatten().map(|v| v.as_ref()[0]).sum()
+}
+
+fn compare<K, V>(name: &str, key: fn(u64) -> K, value: fn(&K) -> V)
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: AsRef<[u64]> + Send + Sync + Clone + Default,
+{
+    let keys: Vec<K> = (0..KEYS).map(key).collect();
+    let queries: Vec<K> = queries(KEYS).into_iter().map(key).collect();
+
+    let soa = {
+        let map: FrozenMap<K, V> = FrozenMap::from_keys_with(keys.clone(), value);
+        time(|| sum(queries.iter().map(|k| map.get(k))))
+    };
+
+    let aos = {
+        let map: FrozenMapAoS<K, V> = FrozenMapAoS::from_keys_with(keys, value);
+        time(|| sum(queries.iter().map(|k| map.get(k))))
+    };
+
+    let per = |d: Duration| d.as_nanos() as f64 / QUERIES as f64;
+
+    println!(
+        "{name:<20} SoA {:>6.1} ns/key   AoS {:>6.1} ns/key   AoS speedup {:.2}x",
+        per(soa),
+        per(aos),
+        soa.as_secs_f64() / aos.as_secs_f64()
+    );
+}
+
+// values carry their key in the first word so the sum can't be folded away
+fn main() {
+    compare::<u64, [u64; 1]>("u64 -> 8B", |k| k, |k| [*k]);
+    compare::<u64, [u64; 4]>("u64 -> 32B", |k| k, |k| [*k; 4]);
+    compare::<u64, [u64; 16]>("u64 -> 128B", |k| k, |k| [*k; 16]);
+    compare::<[u64; 4], [u64; 1]>("32B key -> 8B", |k| [k; 4], |k| [k[0]]);
+    compare::<[u64; 4], [u64; 4]>("32B key -> 32B", |k| [k; 4], |k| *k);
+    compare::<[u64; 4], [u64; 16]>("32B key -> 128B", |k| [k; 4], |k| [k[0]; 16]);
+}
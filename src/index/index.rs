@@ -1,17 +1,72 @@
 use ph::{
-    BuildDefaultSeededHasher,
+    BuildDefaultSeededHasher, BuildSeededHasher,
     phast::{DefaultCompressedArray, Function2, ShiftOnlyWrapped},
     seeds::BitsFast,
 };
-use std::{hash::Hash, marker::PhantomData, mem::MaybeUninit};
+use std::{
+    borrow::Borrow,
+    hash::Hash,
+    marker::PhantomData,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicBool, AtomicIsize, AtomicU64, Ordering},
+};
 
 use bitvec::{bitvec, vec::BitVec};
 
-type Mphf =
-    Function2<BitsFast, ShiftOnlyWrapped<2>, DefaultCompressedArray, BuildDefaultSeededHasher>;
+use crate::map::FrozenMapError;
+use crate::prefetch::prefetch_read;
+
+pub type Mphf = Function2<BitsFast, ShiftOnlyWrapped<2>, DefaultCompressedArray, SeededHasher>;
+
+// default hasher with a per map seed mixed into every level seed ph asks for
+#[derive(Default, Clone, Copy)]
+pub struct SeededHasher {
+    seed: u64,
+}
+
+impl SeededHasher {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl BuildSeededHasher for SeededHasher {
+    type Hasher = <BuildDefaultSeededHasher as BuildSeededHasher>::Hasher;
+
+    #[inline(always)]
+    fn build_hasher(&self, seed: u64) -> Self::Hasher {
+        BuildDefaultSeededHasher::default().build_hasher(seed ^ self.seed)
+    }
+}
+
+// Moves every key to the slot the mphf gives it and maps it to what that slot holds, the mphf
+// is a bijection from n unique keys onto 0..n so every slot is written exactly once
+pub(crate) fn place<K, T, F>(mphf: &Mphf, keys: Vec<K>, mut f: F) -> Vec<T>
+where
+    K: Hash,
+    F: FnMut(K) -> T,
+{
+    let mut slots: Vec<MaybeUninit<T>> = (0..keys.len()).map(|_| MaybeUninit::uninit()).collect();
+
+    keys.into_iter().for_each(|key| {
+        let idx = mphf.get(&key);
+        slots[idx].write(f(key));
+    });
+
+    slots
+        .into_iter()
+        .map(|slot| unsafe { slot.assume_init() })
+        .collect()
+}
 
 pub type VerifiedIndex<K> = FrozenIndex<WithKeys<K>>;
 pub type UnverifiedIndex<K> = FrozenIndex<NoKeys<K>>;
+pub type FingerprintIndex<K, F> = FrozenIndex<FingerprintKeys<K, F>>;
+pub type AtomicIndex<K> = FrozenIndex<AtomicKeys<K>>;
 
 pub struct FrozenIndex<S>
 where
@@ -27,15 +82,69 @@ where
     S: KeyStorage,
     S::Key: Hash + Eq + Clone + Send + Sync + Default,
 {
+    // Borrow requires a borrowed form to hash exactly like the owned key, and the mphf
+    // hashes a single Hash::hash call per level, so Q lands on the same slot as K
+    //
+    // None only for a map built without keys, ph can't look anything up in an empty mphf
     #[inline]
-    pub fn get_index(&self, key: &S::Key) -> usize {
-        self.mphf.get(key)
+    pub fn get_index<Q>(&self, key: &Q) -> Option<usize>
+    where
+        S::Key: Borrow<Q>,
+        Q: Hash + ?Sized,
+    {
+        if self.keys.capacity() == 0 {
+            return None;
+        }
+
+        Some(self.mphf.get(key))
     }
 
     #[inline]
     pub fn len(&self) -> usize {
         self.keys.len()
     }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+impl<S> FrozenIndex<S>
+where
+    S: VerifyKey,
+    S::Key: Hash + Eq + Clone + Send + Sync + Default,
+{
+    // slot of a key the map was built from, dead or alive
+    #[inline]
+    pub fn known_index<Q>(&self, key: &Q) -> Result<usize, FrozenMapError>
+    where
+        S::Key: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.get_index(key).ok_or(FrozenMapError::UnknownKey)?;
+
+        if !self.keys.verify(idx, key) {
+            return Err(FrozenMapError::UnknownKey);
+        }
+
+        Ok(idx)
+    }
+
+    #[inline]
+    pub fn live_index<Q>(&self, key: &Q) -> Result<usize, FrozenMapError>
+    where
+        S::Key: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.known_index(key)?;
+
+        if self.keys.dead_key(idx) {
+            return Err(FrozenMapError::DeadKey);
+        }
+
+        Ok(idx)
+    }
 }
 
 impl<K> FrozenIndex<WithKeys<K>>
@@ -43,14 +152,65 @@ where
     K: Hash + Eq + Clone + Send + Sync + Default,
 {
     #[inline]
-    pub fn contains_key(&self, key: &K) -> bool {
-        let idx = self.get_index(key);
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let Some(idx) = self.get_index(key) else {
+            return false;
+        };
+
+        if self.keys.dead_key(idx) {
+            return false;
+        }
+
+        self.keys.matches(idx, key)
+    }
+}
+
+impl<K> FrozenIndex<AtomicKeys<K>>
+where
+    K: Hash + Eq + Clone + Send + Sync + Default,
+{
+    #[inline]
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let Some(idx) = self.get_index(key) else {
+            return false;
+        };
+
+        if self.keys.dead_key(idx) {
+            return false;
+        }
+
+        self.keys.matches(idx, key)
+    }
+}
+
+impl<K, F> FrozenIndex<FingerprintKeys<K, F>>
+where
+    K: Hash + Eq + Clone + Send + Sync + Default,
+    F: Fingerprint,
+{
+    #[inline]
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + ?Sized,
+    {
+        let Some(idx) = self.get_index(key) else {
+            return false;
+        };
 
         if self.keys.dead_key(idx) {
             return false;
         }
 
-        self.keys.get(idx) == key
+        self.keys.matches(idx, key)
     }
 }
 
@@ -59,41 +219,91 @@ pub trait KeyStorage {
 
     fn get(&self, idx: usize) -> &Self::Key;
     fn len(&self) -> usize;
+    fn capacity(&self) -> usize; // live and dead slots
+    fn dead_count(&self) -> usize {
+        self.capacity().saturating_sub(self.len())
+    }
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
     fn kill(&mut self, idx: usize);
     fn rehydrate(&mut self, idx: usize);
+    fn rehydrate_all(&mut self);
     fn dead_key(&self, idx: usize) -> bool;
+    fn dead_slots(&self) -> impl Iterator<Item = usize>;
+    fn key_bytes(&self) -> usize; // inline size of whatever stands in for the keys
+    fn tombstone_bytes(&self) -> usize;
+    fn generation_bytes(&self) -> usize {
+        0
+    }
+}
+
+// key storages that can tell the key placed in a slot from a foreign key the mphf sends
+// there, NoKeys has nothing to compare against and takes every key
+pub trait VerifyKey: KeyStorage {
+    fn verify<Q>(&self, idx: usize, key: &Q) -> bool
+    where
+        Self::Key: Borrow<Q>,
+        Q: Hash + Eq + ?Sized;
+}
+
+// key storages laid out from keys already placed in slot order
+pub trait FromSlotKeys: KeyStorage + Sized {
+    fn from_slot_keys(keys: Vec<Self::Key>, hasher: SeededHasher) -> Self;
 }
 
 pub struct WithKeys<K> {
     keys: Box<[K]>,
     len: usize,
     tombstone: BitVec,
+    // bumped on every kill so slot handles can detect reaps, only allocated by the first
+    // kill after a handle was given out, until then every slot is at generation 0
+    generations: Option<Box<[u32]>>,
+    handles: AtomicBool,
 }
 
 impl<K> WithKeys<K>
 where
     K: Hash + Eq + Send + Sync + Clone + Default,
 {
-    pub fn new_from_uninit(keys: Vec<MaybeUninit<K>>) -> Self {
-        let n = keys.len();
+    pub fn get_keys(&self) -> Vec<K> {
+        self.keys.to_vec()
+    }
 
-        let keys_k: Box<[K]> = keys // fixed size heap alloc for keys
-            .into_iter()
-            .map(|maybe| unsafe { maybe.assume_init() })
-            .collect::<Vec<K>>()
-            .into_boxed_slice();
+    #[inline]
+    pub fn matches<Q>(&self, idx: usize, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.keys[idx].borrow() == key
+    }
 
-        let tombstone = bitvec![0; n];
+    #[inline(always)]
+    pub fn prefetch(&self, idx: usize) {
+        prefetch_read(self.keys.as_ptr().wrapping_add(idx));
+    }
 
-        Self {
-            keys: keys_k,
-            len: n,
-            tombstone,
-        }
+    #[inline]
+    pub fn as_slice(&self) -> &[K] {
+        &self.keys
     }
 
-    pub fn get_keys(&self) -> Vec<K> {
-        self.keys.to_vec()
+    #[inline]
+    pub fn generation(&self, idx: usize) -> u32 {
+        self.generations
+            .as_ref()
+            .map_or(0, |generations| generations[idx])
+    }
+
+    #[inline] // called for every handle given out, kills start counting generations
+    pub fn track_generations(&self) {
+        self.handles.store(true, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn into_parts(self) -> (Box<[K]>, BitVec) {
+        (self.keys, self.tombstone)
     }
 }
 
@@ -117,6 +327,20 @@ impl<K> NoKeys<K> {
     }
 }
 
+impl<K> FromSlotKeys for WithKeys<K> {
+    fn from_slot_keys(keys: Vec<K>, _: SeededHasher) -> Self {
+        let n = keys.len();
+
+        Self {
+            keys: keys.into_boxed_slice(),
+            len: n,
+            tombstone: bitvec![0; n],
+            generations: None,
+            handles: AtomicBool::new(false),
+        }
+    }
+}
+
 impl<K> KeyStorage for WithKeys<K> {
     type Key = K;
 
@@ -130,11 +354,24 @@ impl<K> KeyStorage for WithKeys<K> {
         self.len
     }
 
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.tombstone.len()
+    }
+
     #[inline]
     fn kill(&mut self, idx: usize) {
         if !self.tombstone[idx] {
             self.tombstone.set(idx, true);
             self.len -= 1;
+
+            if *self.handles.get_mut() {
+                let n = self.keys.len();
+                let generations = self
+                    .generations
+                    .get_or_insert_with(|| vec![0; n].into_boxed_slice());
+                generations[idx] = generations[idx].wrapping_add(1);
+            }
         }
     }
 
@@ -146,10 +383,67 @@ impl<K> KeyStorage for WithKeys<K> {
         }
     }
 
+    #[inline]
+    fn rehydrate_all(&mut self) {
+        self.tombstone.fill(false);
+        self.len = self.tombstone.len();
+    }
+
     #[inline]
     fn dead_key(&self, idx: usize) -> bool {
         self.tombstone[idx]
     }
+
+    #[inline]
+    fn dead_slots(&self) -> impl Iterator<Item = usize> {
+        self.tombstone.iter_ones()
+    }
+
+    #[inline]
+    fn key_bytes(&self) -> usize {
+        size_of_val(&*self.keys)
+    }
+
+    #[inline]
+    fn tombstone_bytes(&self) -> usize {
+        size_of_val(self.tombstone.as_raw_slice())
+    }
+
+    #[inline]
+    fn generation_bytes(&self) -> usize {
+        self.generations.as_deref().map_or(0, size_of_val)
+    }
+}
+
+impl<K> VerifyKey for WithKeys<K>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+{
+    #[inline]
+    fn verify<Q>(&self, idx: usize, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.matches(idx, key)
+    }
+}
+
+impl<K> VerifyKey for NoKeys<K> {
+    #[inline]
+    fn verify<Q>(&self, _: usize, _: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        true
+    }
+}
+
+impl<K> FromSlotKeys for NoKeys<K> {
+    fn from_slot_keys(keys: Vec<K>, _: SeededHasher) -> Self {
+        Self::new(keys.len())
+    }
 }
 
 impl<K> KeyStorage for NoKeys<K> {
@@ -165,6 +459,11 @@ impl<K> KeyStorage for NoKeys<K> {
         self.len
     }
 
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.tombstone.len()
+    }
+
     #[inline]
     fn kill(&mut self, idx: usize) {
         if !self.tombstone[idx] {
@@ -181,8 +480,344 @@ impl<K> KeyStorage for NoKeys<K> {
         }
     }
 
+    #[inline]
+    fn rehydrate_all(&mut self) {
+        self.tombstone.fill(false);
+        self.len = self.tombstone.len();
+    }
+
     #[inline]
     fn dead_key(&self, idx: usize) -> bool {
         self.tombstone[idx]
     }
+
+    #[inline]
+    fn dead_slots(&self) -> impl Iterator<Item = usize> {
+        self.tombstone.iter_ones()
+    }
+
+    #[inline]
+    fn key_bytes(&self) -> usize {
+        0
+    }
+
+    #[inline]
+    fn tombstone_bytes(&self) -> usize {
+        size_of_val(self.tombstone.as_raw_slice())
+    }
+}
+
+// Fingerprint widths, a foreign key lands on some slot and passes verification
+// when its fingerprint collides with the one stored there:
+// u8  ~ 1 byte per key,  false positive rate 2^-8  (~0.39%)
+// u16 ~ 2 bytes per key, false positive rate 2^-16 (~0.0015%)
+// u32 ~ 4 bytes per key, false positive rate 2^-32 (~2.3e-10)
+
+pub trait Fingerprint: Copy + Eq + Default + Send + Sync {
+    fn from_hash(hash: u64) -> Self;
+}
+
+impl Fingerprint for u8 {
+    #[inline(always)]
+    fn from_hash(hash: u64) -> Self {
+        (hash >> 56) as u8
+    }
+}
+
+impl Fingerprint for u16 {
+    #[inline(always)]
+    fn from_hash(hash: u64) -> Self {
+        (hash >> 48) as u16
+    }
+}
+
+impl Fingerprint for u32 {
+    #[inline(always)]
+    fn from_hash(hash: u64) -> Self {
+        (hash >> 32) as u32
+    }
+}
+
+// level seed for fingerprints, kept far away from the small level seeds the mphf uses
+const FINGERPRINT_SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+
+pub struct FingerprintKeys<K, F> {
+    _ghost: PhantomData<K>,
+    fingerprints: Box<[F]>,
+    hasher: SeededHasher,
+    len: usize,
+    tombstone: BitVec,
+}
+
+impl<K, F> FingerprintKeys<K, F>
+where
+    K: Hash,
+    F: Fingerprint,
+{
+    pub fn new(len: usize, hasher: SeededHasher) -> Self {
+        let tombstone = bitvec![0; len];
+
+        Self {
+            _ghost: PhantomData,
+            fingerprints: vec![F::default(); len].into_boxed_slice(),
+            hasher,
+            len,
+            tombstone,
+        }
+    }
+
+    #[inline]
+    pub fn fingerprint<Q>(&self, key: &Q) -> F
+    where
+        K: Borrow<Q>,
+        Q: Hash + ?Sized,
+    {
+        F::from_hash(self.hasher.hash_one(key, FINGERPRINT_SEED))
+    }
+
+    #[inline]
+    pub fn insert(&mut self, idx: usize, key: &K) {
+        self.fingerprints[idx] = self.fingerprint(key);
+    }
+
+    #[inline]
+    pub fn matches<Q>(&self, idx: usize, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + ?Sized,
+    {
+        self.fingerprints[idx] == self.fingerprint(key)
+    }
+}
+
+impl<K, F> FromSlotKeys for FingerprintKeys<K, F>
+where
+    K: Hash,
+    F: Fingerprint,
+{
+    fn from_slot_keys(keys: Vec<K>, hasher: SeededHasher) -> Self {
+        let mut fingerprints = Self::new(keys.len(), hasher);
+
+        keys.iter()
+            .enumerate()
+            .for_each(|(idx, key)| fingerprints.insert(idx, key));
+
+        fingerprints
+    }
+}
+
+impl<K, F> VerifyKey for FingerprintKeys<K, F>
+where
+    K: Hash,
+    F: Fingerprint,
+{
+    #[inline]
+    fn verify<Q>(&self, idx: usize, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.matches(idx, key)
+    }
+}
+
+impl<K, F> KeyStorage for FingerprintKeys<K, F> {
+    type Key = K;
+
+    #[inline]
+    fn get(&self, _: usize) -> &K {
+        unreachable!("fingerprint index does not store keys")
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.tombstone.len()
+    }
+
+    #[inline]
+    fn kill(&mut self, idx: usize) {
+        if !self.tombstone[idx] {
+            self.tombstone.set(idx, true);
+            self.len -= 1;
+        }
+    }
+
+    #[inline]
+    fn rehydrate(&mut self, idx: usize) {
+        if self.tombstone[idx] {
+            self.tombstone.set(idx, false);
+            self.len += 1;
+        }
+    }
+
+    #[inline]
+    fn rehydrate_all(&mut self) {
+        self.tombstone.fill(false);
+        self.len = self.tombstone.len();
+    }
+
+    #[inline]
+    fn dead_key(&self, idx: usize) -> bool {
+        self.tombstone[idx]
+    }
+
+    #[inline]
+    fn dead_slots(&self) -> impl Iterator<Item = usize> {
+        self.tombstone.iter_ones()
+    }
+
+    #[inline]
+    fn key_bytes(&self) -> usize {
+        size_of_val(&*self.fingerprints)
+    }
+
+    #[inline]
+    fn tombstone_bytes(&self) -> usize {
+        size_of_val(self.tombstone.as_raw_slice())
+    }
+}
+
+// Verified keys with an atomic tombstone, reaps and rehydrates go through &self so the
+// index can be shared across threads, the &mut KeyStorage methods forward to them
+
+pub struct AtomicKeys<K> {
+    keys: Box<[K]>,
+    // the tombstone bit flips first and the count follows, so racing reaps and rehydrates
+    // can push it outside 0..=capacity for a moment, signed and clamped when read
+    len: AtomicIsize,
+    tombstone: Box<[AtomicU64]>, // one bit per slot
+}
+
+impl<K> AtomicKeys<K>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+{
+    #[inline]
+    pub fn matches<Q>(&self, idx: usize, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.keys[idx].borrow() == key
+    }
+
+    #[inline] // false when the key was already dead, a racing reap only succeeds once
+    pub fn try_kill(&self, idx: usize) -> bool {
+        let bit = 1 << (idx % 64);
+        let prev = self.tombstone[idx / 64].fetch_or(bit, Ordering::AcqRel);
+
+        if prev & bit != 0 {
+            return false;
+        }
+
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        true
+    }
+
+    #[inline] // false when the key was already alive
+    pub fn try_rehydrate(&self, idx: usize) -> bool {
+        let bit = 1 << (idx % 64);
+        let prev = self.tombstone[idx / 64].fetch_and(!bit, Ordering::AcqRel);
+
+        if prev & bit == 0 {
+            return false;
+        }
+
+        self.len.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+}
+
+impl<K> FromSlotKeys for AtomicKeys<K>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+{
+    fn from_slot_keys(keys: Vec<K>, _: SeededHasher) -> Self {
+        let n = keys.len();
+
+        Self {
+            keys: keys.into_boxed_slice(),
+            len: AtomicIsize::new(n as isize),
+            tombstone: (0..n.div_ceil(64)).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+}
+
+impl<K> VerifyKey for AtomicKeys<K>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+{
+    #[inline]
+    fn verify<Q>(&self, idx: usize, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.matches(idx, key)
+    }
+}
+
+impl<K> KeyStorage for AtomicKeys<K>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+{
+    type Key = K;
+
+    #[inline]
+    fn get(&self, idx: usize) -> &K {
+        &self.keys[idx]
+    }
+
+    #[inline] // a snapshot while other threads reap or rehydrate
+    fn len(&self) -> usize {
+        (self.len.load(Ordering::Relaxed).max(0) as usize).min(self.keys.len())
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.keys.len()
+    }
+
+    #[inline]
+    fn kill(&mut self, idx: usize) {
+        self.try_kill(idx);
+    }
+
+    #[inline]
+    fn rehydrate(&mut self, idx: usize) {
+        self.try_rehydrate(idx);
+    }
+
+    #[inline]
+    fn rehydrate_all(&mut self) {
+        self.tombstone
+            .iter_mut()
+            .for_each(|word| *word.get_mut() = 0);
+        *self.len.get_mut() = self.keys.len() as isize;
+    }
+
+    #[inline]
+    fn dead_key(&self, idx: usize) -> bool {
+        self.tombstone[idx / 64].load(Ordering::Acquire) & (1 << (idx % 64)) != 0
+    }
+
+    #[inline]
+    fn dead_slots(&self) -> impl Iterator<Item = usize> {
+        (0..self.keys.len()).filter(|&idx| self.dead_key(idx))
+    }
+
+    #[inline]
+    fn key_bytes(&self) -> usize {
+        size_of_val(&*self.keys)
+    }
+
+    #[inline]
+    fn tombstone_bytes(&self) -> usize {
+        size_of_val(&*self.tombstone)
+    }
 }
@@ -1,6 +1,11 @@
+#[allow(clippy::module_inception)]
 pub mod index;
 pub use index::*;
 
 pub mod prelude {
-    pub use crate::index::{KeyStorage, NoKeys, UnverifiedIndex, VerifiedIndex, WithKeys};
+    pub use crate::index::{
+        AtomicIndex, AtomicKeys, Fingerprint, FingerprintIndex, FingerprintKeys, FromSlotKeys,
+        KeyStorage, Mphf, NoKeys, SeededHasher, UnverifiedIndex, VerifiedIndex, VerifyKey,
+        WithKeys,
+    };
 }
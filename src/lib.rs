@@ -2,17 +2,20 @@ pub mod index;
 pub mod map;
 pub mod store;
 
+mod prefetch;
 
 #[cfg(test)]
 mod miri_test {
-    use crate::map::FrozenMap;
-
-    use super::*;
+    use crate::map::{
+        ArcFrozenMap, AtomicFrozenMap, BuildError, Entry, FingerprintFrozenMap, FrozenMap,
+        FrozenMapAoS, FrozenMapBuilder, FrozenMapError, MapStats, ReapPolicy, ShardedFrozenMap,
+        SlotState, SnapshotWriter, SyncFrozenMap, UnsafeFrozenMap, UpsertError, UpsertReport,
+    };
+    use crate::store::StripedStore;
 
     #[test]
     fn tester() {
-
-         // std::thread::sleep(std::time::Duration::from_secs(12));
+        // std::thread::sleep(std::time::Duration::from_secs(12));
 
         let keys = vec!["gamma", "delta", "void", "bump"];
 
@@ -74,43 +77,31 @@ mod miri_test {
             println!("Key: {k}");
         });
 
-
-
-
         let k = frozen_map.get(&"gamma").unwrap();
         println!("{}", k);
 
-
-
-
-        let  i = frozen_map.get_mut(&"gamma");
-        let y = i.unwrap();    
+        let i = frozen_map.get_mut(&"gamma");
+        let y = i.unwrap();
         *y += 1;
 
-            let k = frozen_map.get(&"gamma").unwrap();
+        let k = frozen_map.get(&"gamma").unwrap();
         println!("{}", k);
 
-
         let keys = vec!["gamma", "delta", "void", "bump"];
         let vals = vec![1, 2, 3, 4];
 
-        let t: FrozenMap<&str, i32> = FrozenMap::unsafe_init(keys, vals);    
+        let t: FrozenMap<&str, i32> = FrozenMap::try_init(keys, vals).unwrap();
 
         t.iter_keys().for_each(|g| println!("{g}"));
 
         t.iter().for_each(|f| println!("{:?}", f));
-
-
     }
 
-
     #[test]
     fn leak() {
-
-
         // std::thread::sleep(std::time::Duration::from_secs(12));
 
-        let keys = vec![1,2,3,4];
+        let keys = vec![1, 2, 3, 4];
 
         // from_vec ~ Initialized
 
@@ -121,14 +112,1334 @@ mod miri_test {
         let _ = frozen_map.upsert(2, "gadmma".to_string());
         let _ = frozen_map.upsert(3, "gammaa".to_string());
         let _ = frozen_map.upsert(4, "g".to_string());
+    }
 
+    #[test]
+    fn builder() {
+        let keys: Vec<u64> = (0..10_000).collect();
+
+        let builder = FrozenMapBuilder::new()
+            .bits_per_seed(8)
+            .recommended_bucket_size()
+            .threads(1)
+            .seed(42);
+
+        let mut frozen_map: FrozenMap<u64, u64> = FrozenMap::from_vec_with(keys.clone(), &builder);
+
+        for k in &keys {
+            let _ = frozen_map.upsert(*k, k * 2);
+        }
+
+        assert_eq!(frozen_map.len(), keys.len());
+        keys.iter()
+            .for_each(|k| assert_eq!(frozen_map.get(k), Some(&(k * 2))));
 
+        // iteration follows slot order, so it shows where the mphf placed every key
+        let slot_order = |builder: FrozenMapBuilder| -> Vec<u64> {
+            let frozen_map: FrozenMap<u64, u64> =
+                FrozenMap::from_keys_with_builder(keys.clone(), |k| *k, &builder);
+            frozen_map.iter().map(|(k, _)| *k).collect()
+        };
 
+        assert_eq!(slot_order(builder), slot_order(builder));
+        assert_ne!(slot_order(builder), slot_order(builder.seed(7)));
 
+        let index_bytes = |builder: FrozenMapBuilder| {
+            let frozen_map: FrozenMap<u64, u64> = FrozenMap::from_vec_with(keys.clone(), &builder);
+            frozen_map.stats().mphf_bytes
+        };
 
+        assert!(index_bytes(builder.bits_per_seed(4)) < index_bytes(builder.bits_per_seed(12)));
     }
 
-   
+    #[test]
+    fn checked_construction() {
+        let dup = FrozenMap::<&str, u32>::try_from_vec(vec!["a", "b", "a", "c", "b", "a"]);
+        assert_eq!(dup.err(), Some(BuildError::DuplicateKeys(vec!["a", "b"])));
 
-}
+        let empty = FrozenMap::<&str, u32>::try_from_vec(vec![]);
+        assert_eq!(empty.err(), Some(BuildError::Empty));
+
+        let mismatch = FrozenMap::try_init(vec!["a", "b"], vec![1]);
+        assert_eq!(
+            mismatch.err(),
+            Some(BuildError::LengthMismatch { keys: 2, values: 1 })
+        );
+
+        let builder = FrozenMapBuilder::new().dedup(true);
+        let pairs = vec![("a", 1), ("b", 2), ("a", 3)];
+        let frozen_map = FrozenMap::try_from_pairs_with(pairs, &builder).unwrap();
+
+        assert_eq!(frozen_map.len(), 2);
+        assert_eq!(frozen_map.get(&"a"), Some(&3));
+        assert_eq!(frozen_map.get(&"b"), Some(&2));
+    }
+
+    #[test]
+    fn safe_construction() {
+        let pairs: FrozenMap<&str, u32> = [("a", 1), ("b", 2)].into_iter().collect();
+        assert_eq!(pairs.get(&"b"), Some(&2));
+
+        // collecting keeps the last value of a repeated key, like HashMap
+        let pairs: FrozenMap<&str, u32> = [("a", 1), ("b", 2), ("a", 3)].into_iter().collect();
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs.get(&"a"), Some(&3));
+        let pairs: ShardedFrozenMap<&str, u32> = [("a", 1), ("a", 2)].into_iter().collect();
+        assert_eq!(pairs.get(&"a"), Some(2));
+
+        let lens = FrozenMap::from_keys_with(vec!["a", "bb", "ccc"], |k| k.len());
+        assert_eq!(lens.get(&"ccc"), Some(&3));
+
+        let hash_map = std::collections::HashMap::from([(1u64, "one"), (2, "two")]);
+        let from_hash_map = FrozenMap::from(hash_map);
+        assert_eq!(from_hash_map.get(&1), Some(&"one"));
+        assert_eq!(from_hash_map.get(&3), None);
+
+        let unsafe_map: UnsafeFrozenMap<u64, u64> = (0..100).map(|k| (k, k + 1)).collect();
+        assert_eq!(unsafe_map.get(&99), Some(&100));
+
+        // a repeated key panics instead of hanging the mphf build, even in release
+        let dup = || vec![0u64, 1, 2, 0];
+        let panics = |f: fn(Vec<u64>)| std::panic::catch_unwind(move || f(dup())).is_err();
+        assert!(panics(|keys| drop(FrozenMap::<u64, u64>::from_vec(keys))));
+        assert!(panics(|keys| drop(FrozenMap::from_keys_with(keys, |k| *k))));
+        assert!(panics(|keys| drop(UnsafeFrozenMap::<u64, u64>::from_vec(
+            keys
+        ))));
+        assert!(panics(|keys| drop(FrozenMapAoS::<u64, u64>::from_vec(
+            keys
+        ))));
+        assert!(panics(|keys| drop(ShardedFrozenMap::<u64, u64>::from_vec(
+            keys
+        ))));
+
+        let builder = FrozenMapBuilder::new().dedup(true);
+        let deduped = FrozenMap::<u64, u64>::from_vec_with(dup(), &builder);
+        assert_eq!(deduped.len(), 3);
+    }
+
+    #[test]
+    fn typed_errors() {
+        let mut frozen_map: FrozenMap<&str, String> = FrozenMap::from_vec(vec!["a", "b"]);
+
+        let rejected = frozen_map.upsert("z", "zeta".to_string());
+        assert_eq!(
+            rejected,
+            Err(UpsertError {
+                error: FrozenMapError::UnknownKey,
+                key: "z",
+                value: "zeta".to_string(),
+            })
+        );
+
+        assert_eq!(
+            frozen_map.rehydrate_key(&"a"),
+            Err(FrozenMapError::AlreadyAlive)
+        );
+        assert_eq!(frozen_map.reap_key(&"a"), Ok(None));
+        assert_eq!(frozen_map.reap_key(&"a"), Err(FrozenMapError::AlreadyDead));
+
+        let rejected = frozen_map.upsert("a", "alpha".to_string()).unwrap_err();
+        assert_eq!(rejected.error, FrozenMapError::DeadKey);
+        assert_eq!(rejected.into_value(), "alpha");
+
+        assert_eq!(frozen_map.drop_value(&"z"), Err(FrozenMapError::UnknownKey));
+    }
+
+    #[test]
+    fn fingerprint_rejects_foreign_keys() {
+        let keys: Vec<u64> = (0..10_000).collect();
+        let mut frozen_map: FingerprintFrozenMap<u64, u64, u32> =
+            FingerprintFrozenMap::from_keys_with(keys.clone(), |k| k * 3);
+
+        keys.iter()
+            .for_each(|k| assert_eq!(frozen_map.get(k), Some(&(k * 3))));
+
+        // 2^-32 per foreign key, none of these should slip through
+        let foreign = (10_000..20_000u64)
+            .filter(|k| frozen_map.contains(k))
+            .count();
+        assert_eq!(foreign, 0);
+
+        let rejected = frozen_map.upsert(20_000, 1).unwrap_err();
+        assert_eq!(rejected.error, FrozenMapError::UnknownKey);
+
+        assert_eq!(frozen_map.reap_key(&7), Ok(None));
+        assert_eq!(frozen_map.get(&7), None);
+        assert_eq!(frozen_map.len(), keys.len() - 1);
+    }
+
+    #[test]
+    fn borrowing_iterators() {
+        let keys = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let mut frozen_map: FrozenMap<String, Vec<u32>> = FrozenMap::from_vec(keys);
+
+        let _ = frozen_map.upsert("a".to_string(), vec![1]);
+        let _ = frozen_map.upsert("c".to_string(), vec![3]);
+
+        assert_eq!(frozen_map.keys().count(), 3);
+        assert_eq!(frozen_map.values().count(), 2);
+
+        frozen_map.values_mut().for_each(|v| v.push(0));
+        frozen_map
+            .iter_mut()
+            .filter(|(k, _)| k.as_str() == "a")
+            .for_each(|(_, v)| v.push(9));
+
+        let mut pairs: Vec<(&String, &Vec<u32>)> = frozen_map.iter().collect();
+        pairs.sort();
+        assert_eq!(pairs[0], (&"a".to_string(), &vec![1, 0, 9]));
+        assert_eq!(pairs[1], (&"c".to_string(), &vec![3, 0]));
+
+        let mut owned: Vec<(String, Vec<u32>)> = frozen_map.into_iter().collect();
+        owned.sort();
+        assert_eq!(owned.len(), 2);
+        assert_eq!(owned[1], ("c".to_string(), vec![3, 0]));
+    }
+
+    #[test]
+    fn tombstone_aware_iterators() {
+        let mut frozen_map: FrozenMap<u32, u32> = FrozenMap::from_vec(vec![1, 2, 3, 4]);
+
+        let _ = frozen_map.upsert(1, 10);
+        let _ = frozen_map.upsert(2, 20);
+        let _ = frozen_map.reap_key(&2);
+        let _ = frozen_map.reap_key(&3);
+
+        assert_eq!(frozen_map.iter().collect::<Vec<_>>(), vec![(&1, &10)]);
+        assert_eq!(frozen_map.keys().count(), 2);
+        assert_eq!(frozen_map.iter_keys().count(), 4);
+        assert_eq!(
+            frozen_map.iter_uninitialized().collect::<Vec<_>>(),
+            vec![&4]
+        );
+
+        let mut dead: Vec<_> = frozen_map.iter_dead().collect();
+        dead.sort();
+        assert_eq!(dead, vec![(&2, Some(&20)), (&3, None)]);
+
+        let mut slots: Vec<_> = frozen_map.iter_all_slots().collect();
+        slots.sort_by_key(|(k, _)| **k);
+        assert_eq!(
+            slots,
+            vec![
+                (&1, SlotState::Occupied(&10)),
+                (&2, SlotState::Dead(Some(&20))),
+                (&3, SlotState::Dead(None)),
+                (&4, SlotState::Vacant),
+            ]
+        );
+
+        assert_eq!(frozen_map.into_iter().collect::<Vec<_>>(), vec![(1, 10)]);
+    }
+
+    #[test]
+    fn entry_api() {
+        let mut frozen_map: FrozenMap<&str, u32> = FrozenMap::from_vec(vec!["hits", "misses"]);
+
+        for _ in 0..3 {
+            *frozen_map.entry(&"hits").or_insert(0).unwrap() += 1;
+        }
+        assert_eq!(frozen_map.get(&"hits"), Some(&3));
+
+        frozen_map.entry(&"hits").and_modify(|v| *v *= 10);
+        assert_eq!(frozen_map.get(&"hits"), Some(&30));
+
+        assert_eq!(frozen_map.entry(&"misses").get_or_default(), Some(&mut 0));
+        assert!(matches!(frozen_map.entry(&"other"), Entry::Unknown));
+        assert_eq!(frozen_map.entry(&"other").or_insert(1), None);
+
+        let _ = frozen_map.reap_key(&"hits");
+        assert!(matches!(frozen_map.entry(&"hits"), Entry::Dead(_)));
+        assert_eq!(frozen_map.entry(&"hits").or_insert(1), None);
+
+        let revived = frozen_map.entry(&"hits").revive();
+        assert!(matches!(revived, Entry::Occupied(_)));
+        assert_eq!(revived.remove(), Some(30));
+
+        assert!(matches!(frozen_map.entry(&"hits"), Entry::Vacant(_)));
+        assert!(frozen_map.contains(&"hits"));
+    }
+
+    #[test]
+    fn slot_handles() {
+        let mut frozen_map: FrozenMap<&str, u32> = FrozenMap::from_pairs([("a", 1), ("b", 2)]);
+
+        let a = frozen_map.handle(&"a").unwrap();
+        assert_eq!(frozen_map.handle(&"z"), None);
+        assert_eq!(frozen_map.get_by_handle(a), Some(&1));
+
+        *frozen_map.get_mut_by_handle(a).unwrap() += 10;
+        assert_eq!(frozen_map.get(&"a"), Some(&11));
+
+        let _ = frozen_map.reap_key(&"a");
+        let _ = frozen_map.rehydrate_key(&"a");
+
+        // the reap / rehydrate cycle invalidates handles taken before it
+        assert_eq!(frozen_map.get_by_handle(a), None);
+        assert_eq!(
+            frozen_map.reap_by_handle(a),
+            Err(FrozenMapError::StaleHandle)
+        );
+
+        let a = frozen_map.handle(&"a").unwrap();
+        assert_eq!(frozen_map.get_by_handle(a), Some(&11));
+        assert_eq!(frozen_map.reap_by_handle(a), Ok(None));
+        assert!(!frozen_map.contains(&"a"));
+    }
+
+    #[test]
+    fn borrowed_lookups() {
+        let keys: Vec<String> = ["alpha", "beta", "gamma"].map(String::from).to_vec();
+        let mut frozen_map = FrozenMap::from_keys_with(keys.clone(), |k| k.len());
+
+        assert_eq!(frozen_map.get("alpha"), Some(&5));
+        assert!(frozen_map.contains("beta"));
+        assert!(!frozen_map.contains("delta"));
+
+        *frozen_map.get_mut("gamma").unwrap() += 1;
+        assert_eq!(frozen_map.get(&"gamma".to_string()), Some(&6));
+
+        assert_eq!(frozen_map.reap_key("beta"), Ok(None));
+        assert_eq!(frozen_map.get("beta"), None);
+
+        let fingerprints: FingerprintFrozenMap<String, usize> =
+            FingerprintFrozenMap::from_keys_with(keys.clone(), |k| k.len());
+        assert_eq!(fingerprints.get("beta"), Some(&4));
+
+        let unverified: UnsafeFrozenMap<String, usize> =
+            UnsafeFrozenMap::from_keys_with(keys, |k| k.len());
+        assert_eq!(unverified.get("gamma"), Some(&5));
+    }
+
+    #[test]
+    fn batched_lookups() {
+        let keys: Vec<u64> = (0..1_000).collect();
+        let mut frozen_map = FrozenMap::from_keys_with(keys.clone(), |k| k + 1);
+        let _ = frozen_map.reap_key(&10);
+
+        let queries: Vec<u64> = (0..1_100).rev().collect();
+        let batched = frozen_map.get_many(&queries);
+        let looped: Vec<Option<&u64>> = queries.iter().map(|k| frozen_map.get(k)).collect();
+
+        assert_eq!(batched, looped);
+        assert_eq!(batched[0], None);
+        assert_eq!(batched[1_099], Some(&1));
+
+        let unverified: UnsafeFrozenMap<u64, u64> =
+            UnsafeFrozenMap::from_keys_with(keys, |k| k * 2);
+        let batched = unverified.get_many(&[3u64, 999]);
+        assert_eq!(batched, vec![Some(&6), Some(&1998)]);
+    }
+
+    #[test]
+    fn disjoint_mutable_access() {
+        let mut frozen_map: FrozenMap<&str, i64> =
+            FrozenMap::from_pairs([("checking", 100), ("savings", 50), ("closed", 0)]);
+        let _ = frozen_map.reap_key(&"closed");
+
+        if let Some([from, to]) = frozen_map.get_many_mut([&"checking", &"savings"]) {
+            *from -= 30;
+            *to += 30;
+        }
+        assert_eq!(frozen_map.get(&"checking"), Some(&70));
+        assert_eq!(frozen_map.get(&"savings"), Some(&80));
+
+        assert!(
+            frozen_map
+                .get_many_mut([&"checking", &"checking"])
+                .is_none()
+        );
+        assert!(frozen_map.get_many_mut([&"checking", &"closed"]).is_none());
+        assert!(frozen_map.get_many_mut([&"checking", &"unknown"]).is_none());
+
+        assert_eq!(frozen_map.swap_values(&"checking", &"savings"), Ok(()));
+        assert_eq!(frozen_map.get(&"checking"), Some(&80));
+        assert_eq!(frozen_map.get(&"savings"), Some(&70));
+
+        assert_eq!(
+            frozen_map.swap_values(&"checking", &"closed"),
+            Err(FrozenMapError::DeadKey)
+        );
+    }
+
+    #[test]
+    fn counters() {
+        let mut frozen_map: FrozenMap<u64, u64> =
+            FrozenMap::from_pairs((0..10).map(|k| (k, k * 2)).chain((10..12).map(|k| (k, 0))));
+        frozen_map.drop_value(&10).unwrap();
+        frozen_map.drop_value(&11).unwrap();
+
+        assert_eq!(frozen_map.capacity(), 12);
+        assert_eq!(frozen_map.live_len(), 12);
+        assert_eq!(frozen_map.value_count(), 10);
+        assert_eq!(frozen_map.live_value_count(), 10);
+        assert_eq!(frozen_map.dead_count(), 0);
+
+        frozen_map.reap_key(&0).unwrap();
+        frozen_map.reap_key(&10).unwrap();
+        assert_eq!(frozen_map.live_len(), 10);
+        assert_eq!(frozen_map.dead_count(), 2);
+        assert_eq!(frozen_map.value_count(), 10);
+        assert_eq!(frozen_map.live_value_count(), 9);
+
+        // removing a value kept by a dead key
+        frozen_map.drop_value(&0).unwrap();
+        assert_eq!(frozen_map.value_count(), 9);
+        assert_eq!(frozen_map.live_value_count(), 9);
+
+        frozen_map.rehydrate_key(&0).unwrap();
+        frozen_map.upsert(0, 1).unwrap();
+        frozen_map.upsert(1, 1).unwrap();
+        assert_eq!(frozen_map.value_count(), 10);
+        assert_eq!(frozen_map.live_value_count(), 10);
+
+        if let Entry::Occupied(entry) = frozen_map.entry(&2) {
+            let mut dead = entry.reap();
+            assert_eq!(dead.take_value(), Some(4));
+        }
+        assert_eq!(frozen_map.dead_count(), 2);
+        assert_eq!(frozen_map.value_count(), 9);
+        assert_eq!(frozen_map.live_value_count(), 9);
+        assert_eq!(frozen_map.live_value_count(), frozen_map.iter().count());
+
+        let mut unsafe_map: UnsafeFrozenMap<u64, u64> =
+            UnsafeFrozenMap::from_pairs((0..4).map(|k| (k, k)));
+        unsafe_map.reap_key(&3).unwrap();
+        assert_eq!(unsafe_map.capacity(), 4);
+        assert_eq!(unsafe_map.dead_count(), 1);
+        assert_eq!(unsafe_map.live_value_count(), 3);
+
+        let mut fingerprint_map: FingerprintFrozenMap<u64, u64> =
+            FingerprintFrozenMap::from_pairs((0..4).map(|k| (k, k)));
+        fingerprint_map.reap_key(&3).unwrap();
+        fingerprint_map.drop_value(&3).unwrap();
+        assert_eq!(fingerprint_map.dead_count(), 1);
+        assert_eq!(fingerprint_map.value_count(), 3);
+        assert_eq!(fingerprint_map.live_value_count(), 3);
+    }
+
+    #[test]
+    fn value_returning_mutations() {
+        let mut frozen_map: FrozenMap<String, String> =
+            FrozenMap::from_vec(vec!["a".to_string(), "b".to_string(), "c".to_string()]);
+
+        assert_eq!(
+            frozen_map.replace("a".to_string(), "1".to_string()),
+            Ok(None)
+        );
+        assert_eq!(
+            frozen_map.replace("a".to_string(), "2".to_string()),
+            Ok(Some("1".to_string()))
+        );
+        assert_eq!(
+            frozen_map
+                .replace("z".to_string(), "9".to_string())
+                .unwrap_err()
+                .error,
+            FrozenMapError::UnknownKey
+        );
+
+        assert_eq!(
+            frozen_map
+                .insert_if_absent("b".to_string(), "3".to_string())
+                .cloned(),
+            Ok("3".to_string())
+        );
+        let err = frozen_map
+            .insert_if_absent("b".to_string(), "4".to_string())
+            .unwrap_err();
+        assert_eq!(err.error, FrozenMapError::Occupied);
+        assert_eq!(err.into_value(), "4");
+
+        assert_eq!(
+            frozen_map.update_with("a", |v| {
+                v.push('!');
+                v.len()
+            }),
+            Some(2)
+        );
+        assert_eq!(frozen_map.update_with("c", |v| v.push('!')), None);
+
+        assert_eq!(frozen_map.take("a"), Some("2!".to_string()));
+        assert_eq!(frozen_map.take("a"), None);
+        assert_eq!(frozen_map.value_count(), 1);
+
+        frozen_map.reap_key("b").unwrap();
+        assert_eq!(frozen_map.take("b"), None);
+        assert_eq!(
+            frozen_map
+                .replace("b".to_string(), "5".to_string())
+                .unwrap_err()
+                .error,
+            FrozenMapError::DeadKey
+        );
+
+        let mut unsafe_map: UnsafeFrozenMap<u64, u64> = UnsafeFrozenMap::from_vec(vec![1, 2]);
+        assert_eq!(unsafe_map.replace(1, 10), Ok(None));
+        assert_eq!(unsafe_map.replace(1, 11), Ok(Some(10)));
+        assert_eq!(
+            unsafe_map.insert_if_absent(1, 12).unwrap_err().error,
+            FrozenMapError::Occupied
+        );
+        assert_eq!(unsafe_map.update_with(&1, |v| *v += 1), Some(()));
+        assert_eq!(unsafe_map.take(&1), Some(12));
+        assert_eq!(unsafe_map.value_count(), 0);
+        unsafe_map.upsert(2, 20).unwrap();
+        assert_eq!(unsafe_map.drop_value(&2), Ok(()));
+        assert_eq!(unsafe_map.get(&2), None);
+
+        let mut fingerprint_map: FingerprintFrozenMap<u64, u64> =
+            FingerprintFrozenMap::from_vec(vec![1, 2]);
+        assert_eq!(fingerprint_map.insert_if_absent(2, 20).copied(), Ok(20));
+        assert_eq!(fingerprint_map.replace(2, 21), Ok(Some(20)));
+        assert_eq!(fingerprint_map.take(&2), Some(21));
+    }
+
+    #[test]
+    fn reap_policies() {
+        let pairs = || (0..8u64).map(|k| (k, vec![k; 4]));
+
+        let mut frozen_map: FrozenMap<u64, Vec<u64>> = FrozenMap::from_pairs(pairs());
+        assert_eq!(frozen_map.reap_policy(), ReapPolicy::KeepValue);
+        assert_eq!(frozen_map.reap_key(&0), Ok(None));
+        frozen_map.rehydrate_key(&0).unwrap();
+        assert_eq!(frozen_map.get(&0), Some(&vec![0; 4]));
+
+        frozen_map.set_reap_policy(ReapPolicy::ReturnValue);
+        assert_eq!(frozen_map.reap_key(&1), Ok(Some(vec![1; 4])));
+        frozen_map.rehydrate_key(&1).unwrap();
+        assert_eq!(frozen_map.get(&1), None);
+
+        frozen_map.set_reap_policy(ReapPolicy::DropValue);
+        assert_eq!(frozen_map.reap_key(&2), Ok(None));
+        assert_eq!(frozen_map.iter_dead().next(), Some((&2, None)));
+        if let Entry::Occupied(entry) = frozen_map.entry(&3) {
+            assert_eq!(entry.reap().value(), None);
+        }
+
+        // keep values around, then reclaim them in one sweep
+        frozen_map.set_reap_policy(ReapPolicy::KeepValue);
+        for k in 4..7 {
+            frozen_map.reap_key(&k).unwrap();
+        }
+        assert_eq!(frozen_map.value_count(), 5);
+        assert_eq!(frozen_map.purge_dead_values(), 3);
+        assert_eq!(frozen_map.purge_dead_values(), 0);
+        assert_eq!(frozen_map.value_count(), 2);
+        assert_eq!(frozen_map.live_value_count(), 2);
+        assert_eq!(frozen_map.dead_count(), 5);
+
+        let mut unsafe_map: UnsafeFrozenMap<u64, Vec<u64>> = UnsafeFrozenMap::from_pairs(pairs());
+        unsafe_map.set_reap_policy(ReapPolicy::ReturnValue);
+        assert_eq!(unsafe_map.reap_key(&5), Ok(Some(vec![5; 4])));
+        unsafe_map.set_reap_policy(ReapPolicy::KeepValue);
+        unsafe_map.reap_key(&6).unwrap();
+        assert_eq!(unsafe_map.purge_dead_values(), 1);
+        assert_eq!(unsafe_map.value_count(), 6);
+
+        let mut fingerprint_map: FingerprintFrozenMap<u64, Vec<u64>> =
+            FingerprintFrozenMap::from_pairs(pairs());
+        fingerprint_map.set_reap_policy(ReapPolicy::DropValue);
+        assert_eq!(fingerprint_map.reap_key(&7), Ok(None));
+        assert_eq!(fingerprint_map.value_count(), 7);
+        assert_eq!(fingerprint_map.purge_dead_values(), 0);
+    }
+
+    #[test]
+    fn bulk_mutations() {
+        let mut frozen_map: FrozenMap<u64, u64> = FrozenMap::from_pairs((0..100).map(|k| (k, k)));
+
+        frozen_map.retain(|_, v| {
+            *v += 1;
+            *v % 2 == 0
+        });
+        assert_eq!(frozen_map.value_count(), 50);
+        assert_eq!(frozen_map.get(&1), Some(&2));
+        assert_eq!(frozen_map.get(&0), None);
+        assert!(frozen_map.contains(&0));
+
+        assert_eq!(frozen_map.reap_where(|k| *k >= 90), 10);
+        assert_eq!(frozen_map.reap_where(|k| *k >= 90), 0);
+        assert_eq!(frozen_map.len(), 90);
+        assert_eq!(frozen_map.live_value_count(), 45);
+
+        assert_eq!(frozen_map.rehydrate_all(), 10);
+        assert_eq!(frozen_map.len(), 100);
+        assert_eq!(frozen_map.get(&91), Some(&92));
+        assert_eq!(frozen_map.live_value_count(), 50);
+
+        frozen_map.set_reap_policy(ReapPolicy::DropValue);
+        frozen_map.reap_where(|k| *k < 10);
+        assert_eq!(frozen_map.value_count(), 45);
+
+        let mut drained: Vec<u64> = frozen_map.drain_values().collect();
+        drained.sort();
+        assert_eq!(
+            drained,
+            (11..100).step_by(2).map(|k| k + 1).collect::<Vec<_>>()
+        );
+        assert_eq!(frozen_map.value_count(), 0);
+        assert_eq!(frozen_map.dead_count(), 10);
+
+        // a partially consumed drain still empties the map
+        let mut frozen_map: FrozenMap<u64, String> =
+            FrozenMap::from_pairs((0..10).map(|k| (k, k.to_string())));
+        frozen_map.reap_key(&3).unwrap();
+        assert_eq!(frozen_map.drain_values().take(2).count(), 2);
+        assert_eq!(frozen_map.value_count(), 0);
+        assert_eq!(frozen_map.live_value_count(), 0);
+        assert_eq!(frozen_map.rehydrate_all(), 1);
+
+        // a leaked drain leaks its values but leaves the counters in step
+        let mut frozen_map: FrozenMap<u64, String> =
+            FrozenMap::from_pairs((0..10).map(|k| (k, k.to_string())));
+        frozen_map.reap_key(&3).unwrap();
+        frozen_map.reap_key(&4).unwrap();
+        let mut drain = frozen_map.drain_values();
+        drain.next().unwrap();
+        std::mem::forget(drain);
+        assert_eq!(frozen_map.value_count(), 0);
+        assert_eq!(frozen_map.live_value_count(), 0);
+        frozen_map.rehydrate_key(&3).unwrap();
+        assert_eq!(frozen_map.rehydrate_all(), 1);
+        assert_eq!(frozen_map.live_value_count(), 0);
+        frozen_map.upsert(3, "3".to_string()).unwrap();
+        assert_eq!(frozen_map.value_count(), 1);
+        assert_eq!(frozen_map.live_value_count(), 1);
+
+        let mut unsafe_map: UnsafeFrozenMap<u64, String> =
+            UnsafeFrozenMap::from_pairs((0..10).map(|k| (k, k.to_string())));
+        unsafe_map.reap_key(&0).unwrap();
+        unsafe_map.clear_values();
+        assert_eq!(unsafe_map.value_count(), 0);
+        assert_eq!(unsafe_map.rehydrate_all(), 1);
+        assert_eq!(unsafe_map.get(&0), None);
+
+        let mut fingerprint_map: FingerprintFrozenMap<u64, String> =
+            FingerprintFrozenMap::from_pairs((0..10).map(|k| (k, k.to_string())));
+        assert_eq!(fingerprint_map.drain_values().len(), 10);
+        assert_eq!(fingerprint_map.value_count(), 0);
+    }
+
+    #[test]
+    fn batch_upserts() {
+        let mut frozen_map: FrozenMap<&str, u64> = FrozenMap::from_vec(vec!["a", "b", "c", "d"]);
+        frozen_map.upsert("a", 0).unwrap();
+        frozen_map.reap_key("d").unwrap();
+
+        let report = frozen_map.upsert_many([("a", 1), ("b", 2), ("x", 3), ("d", 4), ("b", 5)]);
+        assert_eq!(report.written, 3);
+        assert_eq!(report.replaced, vec!["a", "b"]);
+        assert_eq!(report.unknown, vec![("x", 3)]);
+        assert_eq!(report.dead, vec![("d", 4)]);
+        assert_eq!(report.rejected(), 2);
+        assert!(!report.is_clean());
+        assert_eq!(frozen_map.get("b"), Some(&5));
+
+        // all or nothing
+        let report = frozen_map
+            .try_upsert_many([("a", 10), ("y", 11)])
+            .unwrap_err();
+        assert_eq!(report.written, 0);
+        assert_eq!(report.unknown, vec![("y", 11)]);
+        assert_eq!(report.untouched, vec![("a", 10)]);
+        assert_eq!(frozen_map.get("a"), Some(&1));
+
+        let report = frozen_map.try_upsert_many([("a", 10), ("c", 12)]).unwrap();
+        assert!(report.is_clean());
+        assert_eq!(
+            report,
+            UpsertReport {
+                written: 2,
+                replaced: vec!["a"],
+                unknown: vec![],
+                dead: vec![],
+                untouched: vec![]
+            }
+        );
+        assert_eq!(frozen_map.get("c"), Some(&12));
+        assert_eq!(frozen_map.value_count(), 3);
+
+        let mut unsafe_map: UnsafeFrozenMap<u64, u64> = UnsafeFrozenMap::from_vec(vec![1, 2]);
+        unsafe_map.reap_key(&2).unwrap();
+        let report = unsafe_map.try_upsert_many([(1, 1), (2, 2)]).unwrap_err();
+        assert_eq!(report.dead, vec![(2, 2)]);
+        assert_eq!(report.untouched, vec![(1, 1)]);
+        assert_eq!(unsafe_map.get(&1), None);
+        assert_eq!(unsafe_map.upsert_many([(1, 1), (2, 2)]).written, 1);
+
+        let mut fingerprint_map: FingerprintFrozenMap<u64, u64> =
+            FingerprintFrozenMap::from_vec(vec![1, 2]);
+        let report = fingerprint_map.upsert_many([(1, 1), (1, 2), (3, 3)]);
+        assert_eq!(report.replaced, vec![1]);
+        assert_eq!(report.unknown, vec![(3, 3)]);
+    }
+
+    #[test]
+    fn atomic_map() {
+        use std::sync::{
+            Arc,
+            atomic::{AtomicBool, AtomicU64, Ordering},
+        };
+
+        let keys: Vec<u64> = (0..64).collect();
+        let frozen_map: Arc<AtomicFrozenMap<u64, AtomicU64>> =
+            Arc::new(AtomicFrozenMap::from_vec(keys));
+
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let frozen_map = Arc::clone(&frozen_map);
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        for k in 0..64 {
+                            frozen_map.fetch_add(&k, 1, Ordering::Relaxed).unwrap();
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .for_each(|worker| worker.join().unwrap());
+
+        assert!(
+            frozen_map
+                .iter()
+                .all(|(_, v)| v.load(Ordering::Relaxed) == 400)
+        );
+        assert_eq!(frozen_map.load(&7, Ordering::Relaxed), Some(400));
+        assert_eq!(frozen_map.load(&70, Ordering::Relaxed), None);
+
+        // every thread races for the same reap, exactly one wins
+        let reapers: Vec<_> = (0..4)
+            .map(|_| {
+                let frozen_map = Arc::clone(&frozen_map);
+                std::thread::spawn(move || frozen_map.reap_key(&3).is_ok() as usize)
+            })
+            .collect();
+
+        let wins: usize = reapers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .sum();
+        assert_eq!(wins, 1);
+        assert_eq!(frozen_map.len(), 63);
+        assert_eq!(frozen_map.dead_count(), 1);
+        assert_eq!(
+            frozen_map.store(&3, 1, Ordering::Relaxed),
+            Err(FrozenMapError::DeadKey)
+        );
+        assert_eq!(frozen_map.reap_key(&3), Err(FrozenMapError::AlreadyDead));
+
+        frozen_map.rehydrate_key(&3).unwrap();
+        assert_eq!(frozen_map.swap(&3, 5, Ordering::AcqRel), Ok(400));
+        assert_eq!(
+            frozen_map.compare_exchange(&3, 5, 6, Ordering::AcqRel, Ordering::Acquire),
+            Ok(Ok(5))
+        );
+        assert_eq!(
+            frozen_map.compare_exchange(&3, 5, 7, Ordering::AcqRel, Ordering::Acquire),
+            Ok(Err(6))
+        );
+        assert_eq!(frozen_map.fetch_sub(&3, 6, Ordering::Relaxed), Ok(6));
+        assert_eq!(
+            frozen_map.rehydrate_key(&3),
+            Err(FrozenMapError::AlreadyAlive)
+        );
+
+        // racing reaps and rehydrates of the same keys never push the counts past capacity
+        let workers: Vec<_> = (0..8)
+            .map(|t| {
+                let frozen_map = Arc::clone(&frozen_map);
+                std::thread::spawn(move || {
+                    for i in 0..20_000 {
+                        if (i + t) % 2 == 0 {
+                            let _ = frozen_map.reap_key(&0);
+                        } else {
+                            let _ = frozen_map.rehydrate_key(&0);
+                        }
+                        assert!(frozen_map.len() <= frozen_map.capacity());
+                        assert!(frozen_map.dead_count() <= frozen_map.capacity());
+                    }
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .for_each(|worker| worker.join().unwrap());
+
+        let dead = !frozen_map.contains(&0) as usize;
+        assert_eq!(frozen_map.dead_count(), dead);
+        assert_eq!(frozen_map.len(), 64 - dead);
+
+        let flags: AtomicFrozenMap<&str, AtomicBool> =
+            AtomicFrozenMap::from_pairs([("on", true), ("off", false)]);
+        assert_eq!(flags.load("on", Ordering::Relaxed), Some(true));
+        flags.store("off", true, Ordering::Relaxed).unwrap();
+        assert_eq!(
+            flags
+                .get("off")
+                .map(|flag| flag.fetch_xor(true, Ordering::Relaxed)),
+            Some(true)
+        );
+        assert_eq!(flags.load("off", Ordering::Relaxed), Some(false));
+    }
 
+    #[test]
+    fn sync_map() {
+        use std::sync::Arc;
+
+        for stripes in [1, 3, 64] {
+            let frozen_map: Arc<SyncFrozenMap<u64, Vec<u64>>> = Arc::new(
+                SyncFrozenMap::from_keys_with((0..32).collect(), |_| Vec::new())
+                    .with_stripes(stripes),
+            );
+            assert_eq!(frozen_map.stripes(), stripes.min(32));
+
+            let workers: Vec<_> = (0..4)
+                .map(|t| {
+                    let frozen_map = Arc::clone(&frozen_map);
+                    std::thread::spawn(move || {
+                        for k in 0..32 {
+                            frozen_map.with_value_mut(&k, |v| v.push(t)).unwrap();
+                        }
+                    })
+                })
+                .collect();
+
+            workers
+                .into_iter()
+                .for_each(|worker| worker.join().unwrap());
+
+            for k in 0..32 {
+                assert_eq!(frozen_map.with_value(&k, Vec::len), Some(4));
+            }
+
+            let mut values = frozen_map.get_cloned(&5).unwrap();
+            values.sort();
+            assert_eq!(values, vec![0, 1, 2, 3]);
+        }
+
+        let frozen_map: SyncFrozenMap<u64, u64> = SyncFrozenMap::from_vec((0..1_000).collect());
+        assert!(frozen_map.stripes() <= StripedStore::<u64>::default_stripes());
+
+        let frozen_map: SyncFrozenMap<&str, String> = SyncFrozenMap::from_vec(vec!["a", "b"]);
+        assert_eq!(frozen_map.get_cloned("a"), None);
+        frozen_map.upsert("a", "x".to_string()).unwrap();
+        assert_eq!(
+            frozen_map.upsert("z", "y".to_string()).unwrap_err().error,
+            FrozenMapError::UnknownKey
+        );
+        assert_eq!(frozen_map.get_cloned("a"), Some("x".to_string()));
+        assert!(frozen_map.contains_value("a"));
+
+        assert_eq!(frozen_map.reap_key("a"), Ok(None));
+        assert_eq!(frozen_map.reap_key("a"), Err(FrozenMapError::AlreadyDead));
+        assert_eq!(frozen_map.len(), 1);
+        assert!(!frozen_map.contains("a"));
+        assert_eq!(frozen_map.get_cloned("a"), None);
+        assert_eq!(
+            frozen_map.upsert("a", "y".to_string()).unwrap_err().error,
+            FrozenMapError::DeadKey
+        );
+
+        frozen_map.rehydrate_key("a").unwrap();
+        assert_eq!(frozen_map.get_cloned("a"), Some("x".to_string()));
+        frozen_map.drop_value("a").unwrap();
+        assert_eq!(frozen_map.get_cloned("a"), None);
+
+        let mut frozen_map: SyncFrozenMap<u64, u64> = SyncFrozenMap::from_pairs([(1, 10), (2, 20)]);
+        frozen_map.set_reap_policy(ReapPolicy::ReturnValue);
+        assert_eq!(frozen_map.reap_key(&1), Ok(Some(10)));
+        frozen_map.rehydrate_key(&1).unwrap();
+        assert_eq!(frozen_map.get_cloned(&1), None);
+        assert_eq!(frozen_map.capacity(), 2);
+    }
+
+    #[test]
+    fn snapshots() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::time::Duration;
+
+        let frozen_map: FrozenMap<u64, u64> = FrozenMap::from_pairs((0..16).map(|k| (k, 0)));
+        let mut writer = SnapshotWriter::new(frozen_map);
+        let mut reader = writer.reader();
+
+        writer.upsert(1, 10).unwrap();
+        writer.reap_key(&2).unwrap();
+        assert_eq!(writer.get(&1), Some(&10));
+        assert_eq!(writer.pending(), 2);
+
+        {
+            let snapshot = reader.snapshot();
+            assert_eq!(snapshot.get(&1), Some(&0));
+            assert!(snapshot.contains(&2));
+            assert_eq!(snapshot.len(), 16);
+        }
+
+        writer.publish();
+        assert_eq!(writer.pending(), 0);
+
+        {
+            let snapshot = reader.snapshot();
+            assert_eq!(snapshot.get(&1), Some(&10));
+            assert!(!snapshot.contains(&2));
+            assert_eq!(snapshot.len(), 15);
+        }
+
+        // the replayed side matches after the next publish too
+        writer.rehydrate_key(&2).unwrap();
+        writer.drop_value(&3).unwrap();
+        assert_eq!(
+            writer.upsert(99, 1).unwrap_err().error,
+            FrozenMapError::UnknownKey
+        );
+        writer.publish();
+
+        let snapshot = reader.snapshot();
+        assert_eq!(snapshot.get(&1), Some(&10));
+        assert_eq!(snapshot.get(&2), Some(&0));
+        assert_eq!(snapshot.get(&3), None);
+        assert_eq!(snapshot.iter().count(), 15);
+        drop(snapshot);
+
+        // readers on other threads always see a batch as a whole
+        let readers: Vec<_> = (0..3)
+            .map(|_| {
+                let mut reader = reader.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..2000 {
+                        let snapshot = reader.snapshot();
+                        assert_eq!(snapshot.get(&4), snapshot.get(&5));
+                    }
+                })
+            })
+            .collect();
+
+        for round in 0..200 {
+            writer.upsert(4, round).unwrap();
+            writer.upsert(5, round).unwrap();
+            writer.publish();
+        }
+
+        readers
+            .into_iter()
+            .for_each(|reader| reader.join().unwrap());
+
+        let snapshot = reader.snapshot();
+        assert_eq!(snapshot.get(&4), Some(&199));
+        drop(snapshot);
+
+        // a leaked snapshot times try_publish out, the writes are visible regardless and the
+        // old side is caught up once the reader moves on
+        let mut other = reader.clone();
+        std::mem::forget(reader.snapshot());
+        writer.upsert(6, 1).unwrap();
+        assert!(!writer.try_publish(Duration::from_millis(10)));
+        assert_eq!(writer.pending(), 0);
+        assert_eq!(writer.get(&6), Some(&1));
+        assert_eq!(other.snapshot().get(&6), Some(&1));
+        assert!(!writer.try_publish(Duration::from_millis(10)));
+
+        drop(reader.snapshot());
+        assert!(writer.try_publish(Duration::from_millis(10)));
+
+        // publish waits for open snapshots, readers can still register meanwhile
+        writer.upsert(6, 2).unwrap();
+        let published = AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            let snapshot = reader.snapshot();
+            scope.spawn(|| {
+                writer.publish();
+                published.store(true, Ordering::SeqCst);
+            });
+
+            std::thread::sleep(Duration::from_millis(50));
+            assert!(!published.load(Ordering::SeqCst));
+            let mut late = other.clone();
+            assert!(late.snapshot().contains(&6));
+            assert_eq!(snapshot.get(&6), Some(&1));
+            drop(snapshot);
+        });
+        assert!(published.load(Ordering::SeqCst));
+        assert_eq!(reader.snapshot().get(&6), Some(&2));
+
+        // a leaked snapshot doesn't hold publish up once its reader is gone
+        let mut leaking = writer.reader();
+        std::mem::forget(leaking.snapshot());
+        drop(leaking);
+        writer.upsert(6, 3).unwrap();
+        assert!(writer.try_publish(Duration::from_millis(10)));
+        assert_eq!(reader.snapshot().get(&6), Some(&3));
+    }
+
+    #[test]
+    fn arc_map() {
+        use std::sync::Arc;
+
+        let frozen_map: ArcFrozenMap<&str, Vec<u64>> =
+            ArcFrozenMap::from_pairs([("a", vec![1]), ("b", vec![2])]);
+
+        let held = frozen_map.get("a").unwrap();
+        frozen_map.upsert("a", vec![3]).unwrap();
+        assert_eq!(*held, vec![1]); // the old value lives on with its reader
+        assert_eq!(Arc::strong_count(&held), 1);
+        assert_eq!(*frozen_map.get("a").unwrap(), vec![3]);
+
+        let shared = Arc::new(vec![4]);
+        let old = frozen_map.swap("b", Arc::clone(&shared)).unwrap();
+        assert_eq!(old.as_deref(), Some(&vec![2]));
+        assert!(Arc::ptr_eq(&frozen_map.get("b").unwrap(), &shared));
+
+        assert_eq!(
+            frozen_map.upsert("z", vec![]).unwrap_err().error,
+            FrozenMapError::UnknownKey
+        );
+        frozen_map.drop_value("b").unwrap();
+        assert!(!frozen_map.contains_value("b"));
+        assert_eq!(Arc::strong_count(&shared), 1);
+
+        // writers share the map and race on the same slot
+        let frozen_map = Arc::new(frozen_map);
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let frozen_map = Arc::clone(&frozen_map);
+                std::thread::spawn(move || {
+                    for _ in 0..250 {
+                        frozen_map
+                            .rcu("b", |current| {
+                                let mut next = current.cloned().unwrap_or_default();
+                                next.push(0);
+                                next
+                            })
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        writers
+            .into_iter()
+            .for_each(|writer| writer.join().unwrap());
+        assert_eq!(frozen_map.get("b").unwrap().len(), 1000);
+
+        let mut frozen_map = Arc::into_inner(frozen_map).unwrap();
+        assert_eq!(frozen_map.iter().count(), 2);
+        assert_eq!(frozen_map.reap_key("a"), Ok(None));
+        assert_eq!(frozen_map.get("a"), None);
+        assert_eq!(
+            frozen_map.swap("a", Arc::new(vec![])),
+            Err(FrozenMapError::DeadKey)
+        );
+        frozen_map.rehydrate_key("a").unwrap();
+        assert_eq!(*frozen_map.get("a").unwrap(), vec![3]);
+
+        frozen_map.set_reap_policy(ReapPolicy::ReturnValue);
+        assert_eq!(frozen_map.reap_key("a").unwrap().as_deref(), Some(&vec![3]));
+        assert_eq!(frozen_map.len(), 1);
+        assert_eq!(frozen_map.dead_count(), 1);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_iteration() {
+        use rayon::prelude::*;
+
+        let mut frozen_map: FrozenMap<u64, u64> =
+            FrozenMap::from_pairs((0..10_000).map(|k| (k, k)));
+        frozen_map.reap_where(|k| k % 10 == 0);
+        frozen_map.drop_value(&1).unwrap();
+
+        assert_eq!(frozen_map.par_iter().count(), 8_999);
+        assert_eq!(frozen_map.par_iter().map(|(k, v)| k - v).sum::<u64>(), 0);
+
+        frozen_map.par_values_mut().for_each(|v| *v *= 2);
+        frozen_map.par_for_each_live(|k, v| *v -= k);
+        assert!(frozen_map.iter().all(|(k, v)| k == v));
+
+        frozen_map.par_retain(|k, _| k % 2 == 0);
+        assert_eq!(frozen_map.live_value_count(), 4_000);
+        assert_eq!(frozen_map.value_count(), 5_000); // dead keys keep their values
+        assert_eq!(frozen_map.get(&20), None);
+        assert_eq!(frozen_map.get(&22), Some(&22));
+    }
+
+    #[test]
+    fn sharded_map() {
+        use std::sync::Arc;
+
+        let frozen_map: Arc<ShardedFrozenMap<u64, u64>> = Arc::new(
+            ShardedFrozenMap::from_vec_with((0..1_000).collect(), 4, &FrozenMapBuilder::new()),
+        );
+        assert_eq!(frozen_map.shards(), 4);
+        assert_eq!(frozen_map.capacity(), 1_000);
+        assert_eq!(frozen_map.get(&1), None);
+
+        let workers: Vec<_> = (0..4)
+            .map(|t| {
+                let frozen_map = Arc::clone(&frozen_map);
+                std::thread::spawn(move || {
+                    for k in (t..1_000).step_by(4) {
+                        frozen_map.upsert(k, k * 2).unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .for_each(|worker| worker.join().unwrap());
+
+        assert_eq!(frozen_map.get(&7), Some(14));
+        assert_eq!(frozen_map.with_value_mut(&7, |v| *v += 1), Some(()));
+        assert_eq!(frozen_map.get(&7), Some(15));
+        assert_eq!(
+            frozen_map.upsert(5_000, 1).unwrap_err().error,
+            FrozenMapError::UnknownKey
+        );
+
+        assert_eq!(frozen_map.reap_key(&7), Ok(None));
+        assert_eq!(frozen_map.reap_key(&7), Err(FrozenMapError::AlreadyDead));
+        assert_eq!(frozen_map.reap_key(&5_000), Err(FrozenMapError::UnknownKey));
+        assert!(!frozen_map.contains(&7));
+        assert_eq!(frozen_map.len(), 999);
+
+        let mut pairs: Vec<(u64, u64)> = Vec::new();
+        frozen_map.for_each(|k, v| pairs.push((*k, *v)));
+        pairs.sort();
+        assert_eq!(pairs.len(), 999);
+        assert_eq!(pairs[7], (8, 16));
+
+        let mut iterated: Vec<(u64, u64)> = frozen_map.iter().filter(|(k, _)| k % 2 == 0).collect();
+        iterated.sort();
+        assert_eq!(iterated.len(), 500);
+        assert_eq!(iterated[4], (8, 16));
+
+        frozen_map.rehydrate_key(&7).unwrap();
+        assert_eq!(frozen_map.get(&7), Some(15));
+        frozen_map.drop_value(&7).unwrap();
+        assert!(!frozen_map.contains_value(&7));
+
+        // the shard count is clamped to the key count, the automatic one stays small too
+        let small: ShardedFrozenMap<u64, u64> = ShardedFrozenMap::from_vec((0..100).collect());
+        assert_eq!(small.shards(), 1);
+
+        let mut frozen_map: ShardedFrozenMap<&str, u64> =
+            ShardedFrozenMap::from_pairs_with([("a", 1), ("b", 2)], 8, &FrozenMapBuilder::new());
+        assert_eq!(frozen_map.shards(), 2);
+        assert_eq!(frozen_map.get("a"), Some(1));
+        assert_eq!(frozen_map.get("z"), None);
+        assert_eq!(frozen_map.len(), 2);
+
+        frozen_map.set_reap_policy(ReapPolicy::ReturnValue);
+        assert_eq!(frozen_map.reap_policy(), ReapPolicy::ReturnValue);
+        assert_eq!(frozen_map.reap_key("b"), Ok(Some(2)));
+
+        assert!(matches!(
+            ShardedFrozenMap::<u64, u64>::try_from_vec(vec![1, 2, 1]),
+            Err(BuildError::DuplicateKeys(_))
+        ));
+    }
+
+    #[test]
+    fn stats() {
+        let builder = FrozenMapBuilder::new().threads(2);
+        let mut frozen_map: FrozenMap<u64, u64> =
+            FrozenMap::from_keys_with_builder((0..1_000).collect(), |k| *k, &builder);
+        (0..100).for_each(|k| {
+            frozen_map.reap_key(&k).unwrap();
+        });
+        (500..1_000).for_each(|k| {
+            frozen_map.take(&k);
+        });
+
+        let stats: MapStats = frozen_map.stats();
+        assert_eq!(stats.capacity, 1_000);
+        assert_eq!(stats.key_bytes, 1_000 * 8);
+        assert_eq!(stats.generation_bytes, 0); // no handle was ever given out
+        assert_eq!(stats.value_bytes, 1_000 * 8);
+        assert!(stats.init_bytes >= 1_000 / 8 && stats.tombstone_bytes >= 1_000 / 8);
+        assert!(stats.mphf_bytes > 0 && stats.bits_per_key < 64.0);
+        assert_eq!(stats.value_load, 0.5);
+        assert_eq!(stats.tombstone_load, 0.1);
+        assert_eq!(stats.build.threads, 2);
+        assert_eq!(
+            stats.total_bytes(),
+            stats.mphf_bytes
+                + stats.key_bytes
+                + stats.value_bytes
+                + stats.init_bytes
+                + stats.tombstone_bytes
+                + stats.generation_bytes
+        );
+
+        // handles make reaps count generations, reported on their own
+        let handle = frozen_map.handle(&200).unwrap();
+        frozen_map.reap_key(&200).unwrap();
+        assert_eq!(frozen_map.get_by_handle(handle), None);
+        let stats = frozen_map.stats();
+        assert_eq!(stats.key_bytes, 1_000 * 8);
+        assert_eq!(stats.generation_bytes, 1_000 * 4);
+
+        let frozen_map: UnsafeFrozenMap<u64, u64> = UnsafeFrozenMap::from_vec((0..1_000).collect());
+        let stats = frozen_map.stats();
+        assert_eq!(stats.key_bytes, 0);
+        assert_eq!(stats.value_load, 0.0);
+        assert!(stats.bits_per_key > 0.0);
+    }
+
+    #[test]
+    fn aos_layout() {
+        let mut frozen_map: FrozenMapAoS<String, Vec<u64>> =
+            FrozenMapAoS::from_keys_with((0..100).map(|k| k.to_string()).collect(), |k| {
+                vec![k.parse().unwrap()]
+            });
+        assert_eq!(frozen_map.len(), 100);
+        assert_eq!(frozen_map.value_count(), 100);
+        assert_eq!(frozen_map.get("7"), Some(&vec![7]));
+        assert_eq!(frozen_map.get("700"), None);
+
+        frozen_map.get_mut("7").unwrap().push(8);
+        assert_eq!(frozen_map.get("7"), Some(&vec![7, 8]));
+        assert_eq!(
+            frozen_map
+                .upsert("700".to_string(), vec![])
+                .unwrap_err()
+                .error,
+            FrozenMapError::UnknownKey
+        );
+
+        assert_eq!(frozen_map.reap_key("7"), Ok(None));
+        assert_eq!(frozen_map.reap_key("7"), Err(FrozenMapError::AlreadyDead));
+        assert!(!frozen_map.contains("7"));
+        assert!(!frozen_map.contains_value("7")); // the kept value is hidden with the key
+        assert_eq!(frozen_map.get("7"), None);
+        assert_eq!(
+            frozen_map
+                .upsert("7".to_string(), vec![])
+                .unwrap_err()
+                .error,
+            FrozenMapError::DeadKey
+        );
+        frozen_map.rehydrate_key("7").unwrap();
+        assert_eq!(frozen_map.get("7"), Some(&vec![7, 8]));
+
+        frozen_map.set_reap_policy(ReapPolicy::ReturnValue);
+        assert_eq!(frozen_map.reap_key("8"), Ok(Some(vec![8])));
+        frozen_map.set_reap_policy(ReapPolicy::DropValue);
+        assert_eq!(frozen_map.reap_key("9"), Ok(None));
+        frozen_map.drop_value("10").unwrap();
+        assert_eq!(frozen_map.value_count(), 97);
+        assert_eq!(frozen_map.dead_count(), 2);
+
+        frozen_map.iter_mut().for_each(|(_, v)| v.push(0));
+        assert_eq!(frozen_map.iter().count(), 97);
+        assert!(frozen_map.iter().all(|(_, v)| v.last() == Some(&0)));
+
+        frozen_map.upsert("10".to_string(), vec![10]).unwrap();
+        assert_eq!(frozen_map.value_count(), 98);
+
+        let frozen_map: FrozenMapAoS<u64, u64> = FrozenMapAoS::from_vec(vec![1, 2]);
+        assert_eq!(frozen_map.get(&1), None);
+        assert!(frozen_map.contains(&1));
+    }
+
+    #[test]
+    fn empty_maps() {
+        // an mphf over no keys can't be queried, every lookup on an empty map misses
+        let mut frozen_map: FrozenMap<u64, u64> = FrozenMap::from_pairs(Vec::new());
+        assert_eq!(frozen_map.get(&1), None);
+        assert!(!frozen_map.contains(&1));
+        assert!(!frozen_map.contains_value(&1));
+        assert_eq!(frozen_map.get_many(&[1, 2]), vec![None, None]);
+        assert_eq!(frozen_map.handle(&1), None);
+        assert!(matches!(frozen_map.entry(&1), Entry::Unknown));
+        assert_eq!(
+            frozen_map.upsert(1, 1).unwrap_err().error,
+            FrozenMapError::UnknownKey
+        );
+        assert_eq!(frozen_map.reap_key(&1), Err(FrozenMapError::UnknownKey));
+
+        let frozen_map: FrozenMap<u64, u64> = std::iter::empty().collect();
+        assert_eq!(frozen_map.get(&1), None);
+
+        let mut writer = SnapshotWriter::new(frozen_map);
+        assert_eq!(writer.get(&1), None);
+        assert_eq!(
+            writer.upsert(1, 1).unwrap_err().error,
+            FrozenMapError::UnknownKey
+        );
+        assert_eq!(writer.reader().snapshot().get(&1), None);
+
+        let mut unsafe_map: UnsafeFrozenMap<u64, u64> = UnsafeFrozenMap::from_vec(Vec::new());
+        assert_eq!(unsafe_map.get(&1), None);
+        assert_eq!(unsafe_map.get_many(&[1]), vec![None]);
+        assert_eq!(
+            unsafe_map.upsert(1, 1).unwrap_err().error,
+            FrozenMapError::UnknownKey
+        );
+        assert_eq!(unsafe_map.drop_value(&1), Err(FrozenMapError::UnknownKey));
+
+        let fingerprint_map: FingerprintFrozenMap<u64, u64> = FingerprintFrozenMap::from_pairs([]);
+        assert_eq!(fingerprint_map.get(&1), None);
+        assert!(!fingerprint_map.contains(&1));
+
+        let atomic_map: AtomicFrozenMap<u64> = AtomicFrozenMap::from_vec(Vec::new());
+        assert_eq!(
+            atomic_map.load(&1, std::sync::atomic::Ordering::Relaxed),
+            None
+        );
+        assert!(!atomic_map.contains(&1));
+        assert_eq!(atomic_map.reap_key(&1), Err(FrozenMapError::UnknownKey));
+
+        let sync_map: SyncFrozenMap<u64, u64> = SyncFrozenMap::from_pairs([]);
+        assert_eq!(sync_map.get_cloned(&1), None);
+        assert!(!sync_map.contains(&1));
+        assert_eq!(sync_map.reap_key(&1), Err(FrozenMapError::UnknownKey));
+
+        let arc_map: ArcFrozenMap<u64, u64> = ArcFrozenMap::from_pairs([]);
+        assert_eq!(arc_map.get(&1), None);
+        assert!(!arc_map.contains(&1));
+
+        let sharded_map: ShardedFrozenMap<u64, u64> = ShardedFrozenMap::from_pairs([]);
+        assert_eq!(sharded_map.get(&1), None);
+        assert!(!sharded_map.contains(&1));
+
+        let aos_map: FrozenMapAoS<u64, u64> = FrozenMapAoS::from_pairs([]);
+        assert_eq!(aos_map.get(&1), None);
+        assert!(!aos_map.contains(&1));
+        assert!(!aos_map.contains_value(&1));
+    }
+
+    #[test]
+    fn contains_value_verifies_keys() {
+        // every slot holds a value, so a foreign key always lands on an occupied slot
+        let mut frozen_map: FrozenMap<u64, u64> =
+            FrozenMap::from_keys_with((0..1_000).collect(), |k| *k);
+        assert!(frozen_map.contains_value(&3));
+        assert!((1_000..1_100).all(|k| !frozen_map.contains_value(&k)));
+        frozen_map.reap_key(&3).unwrap();
+        assert!(!frozen_map.contains_value(&3));
+
+        let mut frozen_map: FingerprintFrozenMap<u64, u64, u32> =
+            FingerprintFrozenMap::from_keys_with((0..1_000).collect(), |k| *k);
+        frozen_map.reap_key(&3).unwrap();
+        assert!(!frozen_map.contains_value(&3));
+        assert!(frozen_map.contains_value(&4));
+
+        let frozen_map: SyncFrozenMap<u64, u64> =
+            SyncFrozenMap::from_keys_with((0..1_000).collect(), |k| *k);
+        assert!((1_000..1_100).all(|k| !frozen_map.contains_value(&k)));
+        frozen_map.reap_key(&3).unwrap();
+        assert!(!frozen_map.contains_value(&3));
+
+        let mut frozen_map: FrozenMapAoS<u64, u64> =
+            FrozenMapAoS::from_keys_with((0..1_000).collect(), |k| *k);
+        assert!((1_000..1_100).all(|k| !frozen_map.contains_value(&k)));
+        frozen_map.reap_key(&3).unwrap();
+        assert!(!frozen_map.contains_value(&3));
+    }
+}
@@ -0,0 +1,260 @@
+use std::{borrow::Borrow, collections::HashMap, hash::Hash, sync::Arc};
+
+use crate::index::prelude::*;
+use crate::map::{BuildError, FrozenMapBuilder, FrozenMapError, UpsertError};
+use crate::store::prelude::*;
+
+// ArcFrozenMap  // rcu style values // lock free reads and writes via &self // key verification
+//
+// every slot holds a swappable Arc<V>, readers get a clone of the Arc and writers swap in a
+// new one, a replaced value lives on until the last reader holding it drops its Arc
+//
+// keys follow the WithKeys tombstone, reap_key and rehydrate_key need &mut self
+
+pub struct ArcFrozenMap<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync,
+{
+    index: VerifiedIndex<K>,
+    store: ArcStore<V>,
+    reap_policy: ReapPolicy,
+}
+
+impl<K, V> ArcFrozenMap<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync,
+{
+    constructors!(V);
+
+    fn from_slots<F>(keys: Vec<K>, value: F, builder: &FrozenMapBuilder) -> Self
+    where
+        F: FnMut(&K) -> Option<V>,
+    {
+        let (index, values) = builder.build_index(keys, value);
+
+        Self {
+            index,
+            store: ArcStore::from_slots(values),
+            reap_policy: ReapPolicy::default(),
+        }
+    }
+
+    #[inline]
+    fn live_index<Q>(&self, key: &Q) -> Result<usize, FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self
+            .index
+            .get_index(key)
+            .ok_or(FrozenMapError::UnknownKey)?;
+
+        if !self.index.keys.matches(idx, key) {
+            return Err(FrozenMapError::UnknownKey);
+        }
+
+        if self.index.keys.dead_key(idx) {
+            return Err(FrozenMapError::DeadKey);
+        }
+
+        Ok(idx)
+    }
+
+    #[inline]
+    pub fn get<Q>(&self, key: &Q) -> Option<Arc<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.live_index(key).ok()?;
+        self.store.load(idx)
+    }
+
+    #[inline]
+    pub fn upsert(&self, key: K, value: V) -> Result<(), UpsertError<K, V>> {
+        match self.live_index(&key) {
+            Ok(idx) => {
+                self.store.store(idx, Some(Arc::new(value)));
+                Ok(())
+            }
+            Err(error) => Err(UpsertError { error, key, value }),
+        }
+    }
+
+    #[inline] // stores an already shared value, returns the one it replaced
+    pub fn swap<Q>(&self, key: &Q, value: Arc<V>) -> Result<Option<Arc<V>>, FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.live_index(key)?;
+        Ok(self.store.swap(idx, Some(value)))
+    }
+
+    // read copy update of a single value, f gets the current value (None when the slot is
+    // empty) and may run more than once when writers race, returns the replaced value
+    #[inline]
+    pub fn rcu<Q, F>(&self, key: &Q, f: F) -> Result<Option<Arc<V>>, FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        F: FnMut(Option<&V>) -> V,
+    {
+        let idx = self.live_index(key)?;
+        Ok(self.store.rcu(idx, f))
+    }
+
+    #[inline] // readers still holding the value keep it alive
+    pub fn drop_value<Q>(&self, key: &Q) -> Result<(), FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self
+            .index
+            .get_index(key)
+            .ok_or(FrozenMapError::UnknownKey)?;
+
+        if !self.index.keys.matches(idx, key) {
+            return Err(FrozenMapError::UnknownKey);
+        }
+
+        self.store.store(idx, None);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.index.contains_key(key)
+    }
+
+    #[inline]
+    pub fn contains_value<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.live_index(key).is_ok_and(|idx| self.store.is_set(idx))
+    }
+
+    #[inline] // the value is kept, dropped or returned depending on the reap policy
+    pub fn reap_key<Q>(&mut self, key: &Q) -> Result<Option<Arc<V>>, FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self
+            .index
+            .get_index(key)
+            .ok_or(FrozenMapError::UnknownKey)?;
+
+        if !self.index.keys.matches(idx, key) {
+            return Err(FrozenMapError::UnknownKey);
+        }
+
+        if self.index.keys.dead_key(idx) {
+            return Err(FrozenMapError::AlreadyDead);
+        }
+
+        self.index.keys.kill(idx);
+
+        Ok(match self.reap_policy {
+            ReapPolicy::KeepValue => None,
+            ReapPolicy::DropValue => {
+                self.store.store(idx, None);
+                None
+            }
+            ReapPolicy::ReturnValue => self.store.swap(idx, None),
+        })
+    }
+
+    #[inline]
+    pub fn rehydrate_key<Q>(&mut self, key: &Q) -> Result<(), FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self
+            .index
+            .get_index(key)
+            .ok_or(FrozenMapError::UnknownKey)?;
+
+        if !self.index.keys.matches(idx, key) {
+            return Err(FrozenMapError::UnknownKey);
+        }
+
+        if !self.index.keys.dead_key(idx) {
+            return Err(FrozenMapError::AlreadyAlive);
+        }
+
+        self.index.keys.rehydrate(idx);
+        Ok(())
+    }
+
+    #[inline] // live keys holding a value, each value loaded as the iterator reaches it
+    pub fn iter(&self) -> impl Iterator<Item = (&K, Arc<V>)> {
+        let keys = &self.index.keys;
+
+        (0..keys.capacity())
+            .filter(|&idx| !keys.dead_key(idx))
+            .filter_map(|idx| Some((keys.get(idx), self.store.load(idx)?)))
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.index.keys.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.index.keys.is_empty()
+    }
+
+    // slots, live and dead
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.index.keys.capacity()
+    }
+
+    #[inline]
+    pub fn dead_count(&self) -> usize {
+        self.index.keys.dead_count()
+    }
+
+    #[inline]
+    pub fn reap_policy(&self) -> ReapPolicy {
+        self.reap_policy
+    }
+
+    #[inline]
+    pub fn set_reap_policy(&mut self, reap_policy: ReapPolicy) {
+        self.reap_policy = reap_policy;
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for ArcFrozenMap<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self::from_iter_last_wins(iter)
+    }
+}
+
+impl<K, V, S> From<HashMap<K, V, S>> for ArcFrozenMap<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync,
+{
+    fn from(map: HashMap<K, V, S>) -> Self {
+        Self::from_unique_pairs(map)
+    }
+}
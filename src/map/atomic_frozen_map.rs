@@ -0,0 +1,272 @@
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    hash::Hash,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::index::prelude::*;
+use crate::map::{BuildError, FrozenMapBuilder, FrozenMapError};
+use crate::store::prelude::*;
+
+// AtomicFrozenMap  // lock free // thread safe through &self // key verification
+//
+// the key set is fixed so there is no resizing or probing to guard, every slot is an atomic
+// value plus one bit of an atomic tombstone, share it across threads with an Arc, keys built
+// without a value start at A::Value::default()
+//
+// a write racing a reap of the same key may land right after the reap, the value then stays
+// with the dead key the same way ReapPolicy::KeepValue keeps it
+
+pub struct AtomicFrozenMap<K, A = AtomicU64>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    A: AtomicValue,
+{
+    index: AtomicIndex<K>,
+    store: AtomicStore<A>,
+}
+
+impl<K, A> AtomicFrozenMap<K, A>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    A: AtomicValue,
+{
+    constructors!(A::Value);
+
+    fn from_slots<F>(keys: Vec<K>, value: F, builder: &FrozenMapBuilder) -> Self
+    where
+        F: FnMut(&K) -> Option<A::Value>,
+    {
+        let (index, values) = builder.build_index(keys, value);
+
+        Self {
+            index,
+            store: AtomicStore::from_slots(values),
+        }
+    }
+
+    #[inline]
+    fn live_index<Q>(&self, key: &Q) -> Result<usize, FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self
+            .index
+            .get_index(key)
+            .ok_or(FrozenMapError::UnknownKey)?;
+
+        if !self.index.keys.matches(idx, key) {
+            return Err(FrozenMapError::UnknownKey);
+        }
+
+        if self.index.keys.dead_key(idx) {
+            return Err(FrozenMapError::DeadKey);
+        }
+
+        Ok(idx)
+    }
+
+    #[inline] // the atomic itself, for operations the map does not forward
+    pub fn get<Q>(&self, key: &Q) -> Option<&A>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.live_index(key).ok()?;
+        Some(self.store.get(idx))
+    }
+
+    #[inline]
+    pub fn load<Q>(&self, key: &Q, order: Ordering) -> Option<A::Value>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).map(|atomic| atomic.load(order))
+    }
+
+    #[inline]
+    pub fn store<Q>(&self, key: &Q, value: A::Value, order: Ordering) -> Result<(), FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.live_index(key)?;
+        self.store.get(idx).store(value, order);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn swap<Q>(
+        &self,
+        key: &Q,
+        value: A::Value,
+        order: Ordering,
+    ) -> Result<A::Value, FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.live_index(key)?;
+        Ok(self.store.get(idx).swap(value, order))
+    }
+
+    #[inline] // the inner result is the one of the atomic compare_exchange
+    pub fn compare_exchange<Q>(
+        &self,
+        key: &Q,
+        current: A::Value,
+        new: A::Value,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Result<A::Value, A::Value>, FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.live_index(key)?;
+        Ok(self
+            .store
+            .get(idx)
+            .compare_exchange(current, new, success, failure))
+    }
+
+    #[inline]
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.index.contains_key(key)
+    }
+
+    #[inline] // the value stays with the dead key
+    pub fn reap_key<Q>(&self, key: &Q) -> Result<(), FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self
+            .index
+            .get_index(key)
+            .ok_or(FrozenMapError::UnknownKey)?;
+
+        if !self.index.keys.matches(idx, key) {
+            return Err(FrozenMapError::UnknownKey);
+        }
+
+        if !self.index.keys.try_kill(idx) {
+            return Err(FrozenMapError::AlreadyDead);
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn rehydrate_key<Q>(&self, key: &Q) -> Result<(), FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self
+            .index
+            .get_index(key)
+            .ok_or(FrozenMapError::UnknownKey)?;
+
+        if !self.index.keys.matches(idx, key) {
+            return Err(FrozenMapError::UnknownKey);
+        }
+
+        if !self.index.keys.try_rehydrate(idx) {
+            return Err(FrozenMapError::AlreadyAlive);
+        }
+
+        Ok(())
+    }
+
+    #[inline] // live keys and their atomics, keys reaped or rehydrated meanwhile may be missed
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &A)> {
+        (0..self.index.keys.capacity())
+            .filter(|&idx| !self.index.keys.dead_key(idx))
+            .map(|idx| (self.index.keys.get(idx), self.store.get(idx)))
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.index.keys.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.index.keys.is_empty()
+    }
+
+    // slots, live and dead
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.index.keys.capacity()
+    }
+
+    #[inline]
+    pub fn dead_count(&self) -> usize {
+        self.index.keys.dead_count()
+    }
+}
+
+impl<K, A> AtomicFrozenMap<K, A>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    A: AtomicInteger,
+{
+    #[inline]
+    pub fn fetch_add<Q>(
+        &self,
+        key: &Q,
+        value: A::Value,
+        order: Ordering,
+    ) -> Result<A::Value, FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.live_index(key)?;
+        Ok(self.store.get(idx).fetch_add(value, order))
+    }
+
+    #[inline]
+    pub fn fetch_sub<Q>(
+        &self,
+        key: &Q,
+        value: A::Value,
+        order: Ordering,
+    ) -> Result<A::Value, FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.live_index(key)?;
+        Ok(self.store.get(idx).fetch_sub(value, order))
+    }
+}
+
+impl<K, A> FromIterator<(K, A::Value)> for AtomicFrozenMap<K, A>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    A: AtomicValue,
+{
+    fn from_iter<I: IntoIterator<Item = (K, A::Value)>>(iter: I) -> Self {
+        Self::from_iter_last_wins(iter)
+    }
+}
+
+impl<K, A, S> From<HashMap<K, A::Value, S>> for AtomicFrozenMap<K, A>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    A: AtomicValue,
+{
+    fn from(map: HashMap<K, A::Value, S>) -> Self {
+        Self::from_unique_pairs(map)
+    }
+}
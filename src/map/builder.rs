@@ -0,0 +1,251 @@
+use bitvec::{bitvec, vec::BitVec};
+use ph::phast::{Function2, Params, ShiftOnlyWrapped, bits_per_seed_to_100_bucket_size};
+use ph::seeds::BitsFast;
+use std::{collections::HashSet, hash::Hash};
+
+use crate::index::{FrozenIndex, place, prelude::*};
+use crate::map::BuildError;
+
+// PHast+ tuning for the mphf index, shared by every map constructor
+
+#[derive(Clone, Copy, Debug)]
+pub struct FrozenMapBuilder {
+    bits_per_seed: u8,
+    bucket_size100: u16,
+    threads: usize,
+    seed: u64,
+    dedup: bool,
+}
+
+impl Default for FrozenMapBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrozenMapBuilder {
+    pub const DEFAULT_BITS_PER_SEED: u8 = 10;
+    pub const DEFAULT_BUCKET_SIZE100: u16 = bits_per_seed_to_100_bucket_size(8);
+
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            bits_per_seed: Self::DEFAULT_BITS_PER_SEED,
+            bucket_size100: Self::DEFAULT_BUCKET_SIZE100,
+            threads: std::thread::available_parallelism().map_or(1, |v| v.into()),
+            seed: 0,
+            dedup: false,
+        }
+    }
+
+    // more bits per seed ~ faster lookups and builds, bigger index (1..=16)
+    #[inline]
+    pub fn bits_per_seed(mut self, bits_per_seed: u8) -> Self {
+        assert!(
+            (1..=16).contains(&bits_per_seed),
+            "bits per seed must be within 1..=16"
+        );
+        self.bits_per_seed = bits_per_seed;
+        self
+    }
+
+    // average bucket size * 100, bigger buckets ~ smaller index, slower builds
+    #[inline]
+    pub fn bucket_size100(mut self, bucket_size100: u16) -> Self {
+        assert!(bucket_size100 > 0, "bucket size must be non zero");
+        self.bucket_size100 = bucket_size100;
+        self
+    }
+
+    // pick the bucket size ph recommends for the current bits per seed
+    #[inline]
+    pub fn recommended_bucket_size(mut self) -> Self {
+        self.bucket_size100 = bits_per_seed_to_100_bucket_size(self.bits_per_seed);
+        self
+    }
+
+    // 1 builds single threaded
+    #[inline]
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    #[inline]
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    // checked constructors drop repeated keys instead of failing, the last value per key wins
+    #[inline]
+    pub fn dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    #[inline]
+    pub(crate) fn get_threads(&self) -> usize {
+        self.threads
+    }
+
+    #[inline]
+    pub(crate) fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    // ph never finishes building over a repeated key, every constructor checks them before this
+    #[inline]
+    pub(crate) fn build_mphf<K>(&self, keys: &[K]) -> Mphf
+    where
+        K: Hash + Eq + Send + Sync + Clone,
+    {
+        debug_assert!(
+            shadowed(keys, |k| k, false).not_any(),
+            "build_mphf: duplicate keys"
+        );
+
+        Function2::with_slice_p_threads_hash_sc(
+            keys,
+            &Params::new(BitsFast(self.bits_per_seed), self.bucket_size100),
+            self.threads,
+            SeededHasher::new(self.seed),
+            ShiftOnlyWrapped::<2>,
+        )
+    }
+
+    // mphf over the keys with every key moved to its slot, value(key) gives what the value
+    // slot of the key starts with, the values come back in slot order
+    pub(crate) fn build_index<S, T, F>(
+        &self,
+        keys: Vec<S::Key>,
+        mut value: F,
+    ) -> (FrozenIndex<S>, Vec<T>)
+    where
+        S: FromSlotKeys,
+        S::Key: Hash + Eq + Send + Sync + Clone + Default,
+        F: FnMut(&S::Key) -> T,
+    {
+        let mphf = self.build_mphf(&keys);
+
+        let (keys, values) = place(&mphf, keys, |key| {
+            let value = value(&key);
+            (key, value)
+        })
+        .into_iter()
+        .unzip();
+
+        let keys = S::from_slot_keys(keys, SeededHasher::new(self.seed));
+        (FrozenIndex { mphf, keys }, values)
+    }
+
+    // the mphf can't be built over duplicates, so checked constructors run keys through here first
+    pub(crate) fn check_keys<K>(&self, keys: Vec<K>) -> Result<Vec<K>, BuildError<K>>
+    where
+        K: Hash + Eq + Clone,
+    {
+        if keys.is_empty() {
+            return Err(BuildError::Empty);
+        }
+
+        let shadowed = shadowed(&keys, |k| k, false);
+        self.resolve(keys, shadowed, |k| k)
+    }
+
+    // unchecked constructors still can't hand ph duplicates, it never finishes and a repeated
+    // key would leave a slot unwritten, so they panic instead, dedup builders drop the repeats
+    pub(crate) fn unique_keys<K>(&self, keys: Vec<K>) -> Vec<K>
+    where
+        K: Hash + Eq + Clone,
+    {
+        if keys.is_empty() {
+            return keys;
+        }
+
+        match self.check_keys(keys) {
+            Ok(keys) => keys,
+            Err(_) => panic!("duplicate keys, use the try_ constructors or a dedup builder"),
+        }
+    }
+
+    pub(crate) fn check_pairs<K, V>(
+        &self,
+        pairs: Vec<(K, V)>,
+    ) -> Result<(Vec<K>, Vec<V>), BuildError<K>>
+    where
+        K: Hash + Eq + Clone,
+    {
+        if pairs.is_empty() {
+            return Err(BuildError::Empty);
+        }
+
+        let shadowed = shadowed(&pairs, |(k, _)| k, true);
+        Ok(self
+            .resolve(pairs, shadowed, |(k, _)| k)?
+            .into_iter()
+            .unzip())
+    }
+
+    #[inline]
+    pub(crate) fn check_lengths<K>(&self, keys: usize, values: usize) -> Result<(), BuildError<K>> {
+        if keys != values {
+            return Err(BuildError::LengthMismatch { keys, values });
+        }
+
+        Ok(())
+    }
+
+    fn resolve<T, K>(
+        &self,
+        items: Vec<T>,
+        shadowed: BitVec,
+        key: impl Fn(&T) -> &K,
+    ) -> Result<Vec<T>, BuildError<K>>
+    where
+        K: Hash + Eq + Clone,
+    {
+        if shadowed.not_any() {
+            return Ok(items);
+        }
+
+        if !self.dedup {
+            let mut reported = HashSet::new();
+            let duplicates = items
+                .iter()
+                .zip(shadowed.iter())
+                .filter(|(_, s)| **s)
+                .map(|(item, _)| key(item))
+                .filter(|k| reported.insert(*k))
+                .cloned()
+                .collect();
+
+            return Err(BuildError::DuplicateKeys(duplicates));
+        }
+
+        Ok(items
+            .into_iter()
+            .zip(shadowed)
+            .filter_map(|(item, s)| (!s).then_some(item))
+            .collect())
+    }
+}
+
+// marks every repeat of a key except the occurrence that is kept (first or last)
+fn shadowed<T, K>(items: &[T], key: impl Fn(&T) -> &K, keep_last: bool) -> BitVec
+where
+    K: Hash + Eq,
+{
+    let n = items.len();
+    let mut seen = HashSet::with_capacity(n);
+    let mut shadowed = bitvec![0; n];
+
+    for step in 0..n {
+        let i = if keep_last { n - 1 - step } else { step };
+
+        if !seen.insert(key(&items[i])) {
+            shadowed.set(i, true);
+        }
+    }
+
+    shadowed
+}
@@ -0,0 +1,126 @@
+// The constructor set every map shares, expanded inside the map's impl block on top of its
+// own from_slots(keys, value, builder), which builds the index and places the values
+//
+// ShardedFrozenMap expands it with a shard count, constructors!(V, shards = 0), the _with
+// constructors then take the count before the builder and the others pass the default
+//
+// duplicate keys make the unchecked constructors panic and the try_ ones return an error, a
+// dedup builder drops them in both
+
+macro_rules! constructors {
+    ($value:ty $(, $shards:ident = $default:expr)?) => {
+        #[inline]
+        pub fn from_vec(keys: Vec<K>) -> Self {
+            Self::from_vec_with(keys, $($default,)? &FrozenMapBuilder::default())
+        }
+
+        #[inline] // panics on duplicate keys unless the builder dedups, see try_from_vec
+        pub fn from_vec_with(keys: Vec<K>, $($shards: usize,)? builder: &FrozenMapBuilder) -> Self {
+            Self::from_slots(builder.unique_keys(keys), |_| None, $($shards,)? builder)
+        }
+
+        #[inline] // panics on duplicate keys unless the builder dedups
+        pub fn from_keys_with<G>(keys: Vec<K>, f: G) -> Self
+        where
+            G: FnMut(&K) -> $value,
+        {
+            Self::from_keys_with_builder(keys, f, $($default,)? &FrozenMapBuilder::default())
+        }
+
+        #[inline]
+        pub fn from_keys_with_builder<G>(
+            keys: Vec<K>,
+            mut f: G,
+            $($shards: usize,)?
+            builder: &FrozenMapBuilder,
+        ) -> Self
+        where
+            G: FnMut(&K) -> $value,
+        {
+            Self::from_slots(builder.unique_keys(keys), |k| Some(f(k)), $($shards,)? builder)
+        }
+
+        #[inline] // panics on duplicate keys unless the builder dedups, see try_from_pairs
+        pub fn from_pairs<I>(pairs: I) -> Self
+        where
+            I: IntoIterator<Item = (K, $value)>,
+        {
+            Self::from_pairs_with(pairs, $($default,)? &FrozenMapBuilder::default())
+        }
+
+        #[inline]
+        pub fn from_pairs_with<I>(pairs: I, $($shards: usize,)? builder: &FrozenMapBuilder) -> Self
+        where
+            I: IntoIterator<Item = (K, $value)>,
+        {
+            let pairs: Vec<(K, $value)> = pairs.into_iter().collect();
+
+            if pairs.is_empty() {
+                return Self::from_slots(Vec::new(), |_| None, $($shards,)? builder);
+            }
+
+            match Self::try_from_pairs_with(pairs, $($shards,)? builder) {
+                Ok(map) => map,
+                Err(_) => {
+                    panic!("from_pairs: duplicate keys, use try_from_pairs or a dedup builder")
+                }
+            }
+        }
+
+        #[inline]
+        pub fn try_from_vec(keys: Vec<K>) -> Result<Self, BuildError<K>> {
+            Self::try_from_vec_with(keys, $($default,)? &FrozenMapBuilder::default())
+        }
+
+        #[inline]
+        pub fn try_from_vec_with(
+            keys: Vec<K>,
+            $($shards: usize,)?
+            builder: &FrozenMapBuilder,
+        ) -> Result<Self, BuildError<K>> {
+            let keys = builder.check_keys(keys)?;
+            Ok(Self::from_slots(keys, |_| None, $($shards,)? builder))
+        }
+
+        #[inline]
+        pub fn try_from_pairs(pairs: Vec<(K, $value)>) -> Result<Self, BuildError<K>> {
+            Self::try_from_pairs_with(pairs, $($default,)? &FrozenMapBuilder::default())
+        }
+
+        #[inline]
+        pub fn try_from_pairs_with(
+            pairs: Vec<(K, $value)>,
+            $($shards: usize,)?
+            builder: &FrozenMapBuilder,
+        ) -> Result<Self, BuildError<K>> {
+            let (keys, values) = builder.check_pairs(pairs)?;
+            let mut values = values.into_iter();
+            Ok(Self::from_slots(keys, |_| values.next(), $($shards,)? builder))
+        }
+
+        // FromIterator, repeated keys keep their last value like the std maps
+        #[inline]
+        fn from_iter_last_wins<I>(pairs: I) -> Self
+        where
+            I: IntoIterator<Item = (K, $value)>,
+        {
+            Self::from_pairs_with(pairs, $($default,)? &FrozenMapBuilder::new().dedup(true))
+        }
+
+        // From<HashMap>, the keys are already unique so the duplicate check is skipped
+        #[inline]
+        fn from_unique_pairs<I>(pairs: I) -> Self
+        where
+            I: IntoIterator<Item = (K, $value)>,
+        {
+            let (keys, values): (Vec<K>, Vec<$value>) = pairs.into_iter().unzip();
+            let mut values = values.into_iter();
+            Self::from_slots(
+                keys,
+                |_| values.next(),
+                $($default,)?
+                &FrozenMapBuilder::default(),
+            )
+        }
+    };
+}
@@ -0,0 +1,250 @@
+use std::hash::Hash;
+
+use crate::index::prelude::*;
+use crate::store::prelude::*;
+
+// Entry API for FrozenMap, the slot is resolved once and every entry
+// operation reuses it instead of going through the mphf again
+
+pub enum Entry<'a, K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    Occupied(OccupiedEntry<'a, K, V>), // live key holding a value
+    Vacant(VacantEntry<'a, K, V>),     // live key without a value
+    Dead(DeadEntry<'a, K, V>),         // reaped key
+    Unknown,                           // key is not part of the map
+}
+
+pub struct OccupiedEntry<'a, K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    slot: Slot<'a, K, V>,
+}
+
+pub struct VacantEntry<'a, K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    slot: Slot<'a, K, V>,
+}
+
+pub struct DeadEntry<'a, K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    slot: Slot<'a, K, V>,
+}
+
+pub(crate) struct Slot<'a, K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    pub(crate) index: &'a mut VerifiedIndex<K>,
+    pub(crate) store: &'a mut Store<V>,
+    pub(crate) idx: usize,
+    pub(crate) reap_policy: ReapPolicy,
+}
+
+impl<'a, K, V> Slot<'a, K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    // classifies an already verified slot
+    #[inline]
+    pub(crate) fn into_entry(self) -> Entry<'a, K, V> {
+        if self.index.keys.dead_key(self.idx) {
+            Entry::Dead(DeadEntry { slot: self })
+        } else if self.store.get_value(self.idx).is_some() {
+            Entry::Occupied(OccupiedEntry { slot: self })
+        } else {
+            Entry::Vacant(VacantEntry { slot: self })
+        }
+    }
+
+    #[inline]
+    fn key(&self) -> &K {
+        self.index.keys.get(self.idx)
+    }
+
+    // the dead entry keeps the value under ReturnValue, take_value moves it out
+    #[inline]
+    fn reap(self) -> Self {
+        self.index.keys.kill(self.idx);
+        self.store.reap(self.idx, self.reap_policy.without_return());
+        self
+    }
+}
+
+impl<'a, K, V> Entry<'a, K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    #[inline]
+    pub fn key(&self) -> Option<&K> {
+        match self {
+            Entry::Occupied(entry) => Some(entry.key()),
+            Entry::Vacant(entry) => Some(entry.key()),
+            Entry::Dead(entry) => Some(entry.key()),
+            Entry::Unknown => None,
+        }
+    }
+
+    // None for dead and unknown keys, they can't hold a new value
+    #[inline]
+    pub fn or_insert(self, value: V) -> Option<&'a mut V> {
+        self.or_insert_with(|| value)
+    }
+
+    #[inline]
+    pub fn or_insert_with<F>(self, f: F) -> Option<&'a mut V>
+    where
+        F: FnOnce() -> V,
+    {
+        match self {
+            Entry::Occupied(entry) => Some(entry.into_mut()),
+            Entry::Vacant(entry) => Some(entry.insert(f())),
+            Entry::Dead(_) | Entry::Unknown => None,
+        }
+    }
+
+    #[inline]
+    pub fn get_or_default(self) -> Option<&'a mut V> {
+        self.or_insert_with(V::default)
+    }
+
+    #[inline]
+    pub fn and_modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut V),
+    {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+
+        self
+    }
+
+    // rehydrates a dead key, the entry becomes occupied or vacant depending on the kept value
+    #[inline]
+    pub fn revive(self) -> Self {
+        match self {
+            Entry::Dead(entry) => entry.revive(),
+            entry => entry,
+        }
+    }
+
+    // moves the value out of an occupied or dead slot, the key stays as it is
+    #[inline]
+    pub fn remove(self) -> Option<V> {
+        match self {
+            Entry::Occupied(entry) => Some(entry.remove()),
+            Entry::Dead(mut entry) => entry.take_value(),
+            Entry::Vacant(_) | Entry::Unknown => None,
+        }
+    }
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    #[inline]
+    pub fn key(&self) -> &K {
+        self.slot.key()
+    }
+
+    #[inline]
+    pub fn get(&self) -> &V {
+        self.slot.store.get_value(self.slot.idx).unwrap()
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut V {
+        self.slot.store.get_mut_value(self.slot.idx).unwrap()
+    }
+
+    #[inline]
+    pub fn into_mut(self) -> &'a mut V {
+        self.slot.store.get_mut_value(self.slot.idx).unwrap()
+    }
+
+    #[inline] // returns the replaced value
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    #[inline]
+    pub fn remove(self) -> V {
+        self.slot.store.take(self.slot.idx).unwrap()
+    }
+
+    // KeepValue and ReturnValue leave the value with the dead key, DeadEntry::take_value
+    // moves it out, DropValue drops it here
+    #[inline]
+    pub fn reap(self) -> DeadEntry<'a, K, V> {
+        DeadEntry {
+            slot: self.slot.reap(),
+        }
+    }
+}
+
+impl<'a, K, V> VacantEntry<'a, K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    #[inline]
+    pub fn key(&self) -> &K {
+        self.slot.key()
+    }
+
+    #[inline]
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.slot.store.insert(self.slot.idx, value)
+    }
+
+    #[inline]
+    pub fn reap(self) -> DeadEntry<'a, K, V> {
+        DeadEntry {
+            slot: self.slot.reap(),
+        }
+    }
+}
+
+impl<'a, K, V> DeadEntry<'a, K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    #[inline]
+    pub fn key(&self) -> &K {
+        self.slot.key()
+    }
+
+    #[inline] // value kept by the reaped key, if any
+    pub fn value(&self) -> Option<&V> {
+        self.slot.store.get_value(self.slot.idx)
+    }
+
+    #[inline]
+    pub fn take_value(&mut self) -> Option<V> {
+        self.slot.store.take_dead(self.slot.idx)
+    }
+
+    #[inline]
+    pub fn revive(self) -> Entry<'a, K, V> {
+        self.slot.index.keys.rehydrate(self.slot.idx);
+        self.slot.store.rehydrate(self.slot.idx);
+        self.slot.into_entry()
+    }
+}
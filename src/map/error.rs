@@ -0,0 +1,138 @@
+use std::fmt;
+
+// Errors returned by map operations
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FrozenMapError {
+    UnknownKey,   // key was not part of the set the map was built from
+    DeadKey,      // key is tombstoned
+    AlreadyDead,  // reap on a tombstoned key
+    AlreadyAlive, // rehydrate on a live key
+    StaleHandle,  // slot handle taken before the key was reaped
+    Occupied,     // insert_if_absent on a key that already holds a value
+}
+
+impl fmt::Display for FrozenMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrozenMapError::UnknownKey => write!(f, "key does not exist"),
+            FrozenMapError::DeadKey => write!(f, "key is dead"),
+            FrozenMapError::AlreadyDead => write!(f, "key is already dead"),
+            FrozenMapError::AlreadyAlive => write!(f, "key is already alive"),
+            FrozenMapError::StaleHandle => write!(f, "slot handle is stale"),
+            FrozenMapError::Occupied => write!(f, "key already holds a value"),
+        }
+    }
+}
+
+impl std::error::Error for FrozenMapError {}
+
+// a rejected upsert hands the owned key and value back to the caller
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpsertError<K, V> {
+    pub error: FrozenMapError,
+    pub key: K,
+    pub value: V,
+}
+
+impl<K, V> UpsertError<K, V> {
+    #[inline]
+    pub fn into_value(self) -> V {
+        self.value
+    }
+}
+
+impl<K, V> fmt::Display for UpsertError<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to upsert, {}", self.error)
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> std::error::Error for UpsertError<K, V> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+// outcome of a batch upsert, rejected pairs are handed back like UpsertError does and
+// replaced values are dropped, a failed all or nothing batch also hands back the valid pairs
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpsertReport<K, V> {
+    pub written: usize,
+    pub replaced: Vec<K>,       // written keys that already held a value
+    pub unknown: Vec<(K, V)>,   // keys that are not part of the map
+    pub dead: Vec<(K, V)>,      // tombstoned keys
+    pub untouched: Vec<(K, V)>, // valid pairs of a try_upsert_many batch that was not written
+}
+
+impl<K, V> Default for UpsertReport<K, V> {
+    fn default() -> Self {
+        Self {
+            written: 0,
+            replaced: Vec::new(),
+            unknown: Vec::new(),
+            dead: Vec::new(),
+            untouched: Vec::new(),
+        }
+    }
+}
+
+impl<K, V> UpsertReport<K, V> {
+    #[inline] // every pair of the batch was written
+    pub fn is_clean(&self) -> bool {
+        self.unknown.is_empty() && self.dead.is_empty()
+    }
+
+    #[inline]
+    pub fn rejected(&self) -> usize {
+        self.unknown.len() + self.dead.len()
+    }
+
+    #[inline]
+    pub(crate) fn write(&mut self, key: K, old: Option<V>) {
+        self.written += 1;
+
+        if old.is_some() {
+            self.replaced.push(key);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn hand_back(&mut self, key: K, value: V) {
+        self.untouched.push((key, value));
+    }
+
+    #[inline]
+    pub(crate) fn reject(&mut self, error: FrozenMapError, key: K, value: V) {
+        match error {
+            FrozenMapError::DeadKey => self.dead.push((key, value)),
+            _ => self.unknown.push((key, value)),
+        }
+    }
+}
+
+// Errors returned by the checked (try_) constructors
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError<K> {
+    Empty,
+    LengthMismatch { keys: usize, values: usize },
+    DuplicateKeys(Vec<K>), // each offending key is listed once
+}
+
+impl<K: fmt::Debug> fmt::Display for BuildError<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::Empty => write!(f, "cannot build a frozen map from an empty key set"),
+            BuildError::LengthMismatch { keys, values } => {
+                write!(f, "got {keys} keys but {values} values")
+            }
+            BuildError::DuplicateKeys(keys) => write!(f, "duplicate keys: {keys:?}"),
+        }
+    }
+}
+
+impl<K: fmt::Debug> std::error::Error for BuildError<K> {}
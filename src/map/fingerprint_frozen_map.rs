@@ -0,0 +1,129 @@
+use std::{borrow::Borrow, collections::HashMap, hash::Hash};
+
+use crate::index::prelude::*;
+use crate::map::{BuildError, FrozenMapBuilder, FrozenMapError, UpsertError, UpsertReport};
+use crate::store::prelude::*;
+
+// FingerprintFrozenMap  // low overhead // not thread safe // probabilistic key verification
+
+pub struct FingerprintFrozenMap<K, V, F = u16>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+    F: Fingerprint,
+{
+    index: FingerprintIndex<K, F>,
+    store: Store<V>,
+    reap_policy: ReapPolicy,
+}
+
+impl<K, V, F> FingerprintFrozenMap<K, V, F>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+    F: Fingerprint,
+{
+    constructors!(V);
+    mutations!(V);
+
+    fn from_slots<G>(keys: Vec<K>, value: G, builder: &FrozenMapBuilder) -> Self
+    where
+        G: FnMut(&K) -> Option<V>,
+    {
+        let (index, values) = builder.build_index(keys, value);
+
+        Self {
+            index,
+            store: Store::from_slots(values),
+            reap_policy: ReapPolicy::default(),
+        }
+    }
+
+    #[inline]
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.index.get_index(key)?;
+
+        if self.index.keys.dead_key(idx) {
+            return None;
+        }
+
+        if !self.index.keys.matches(idx, key) {
+            return None;
+        }
+
+        self.store.get_value(idx)
+    }
+
+    #[inline]
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.index.get_index(key)?;
+
+        if self.index.keys.dead_key(idx) {
+            return None;
+        }
+
+        if !self.index.keys.matches(idx, key) {
+            return None;
+        }
+
+        self.store.get_mut_value(idx)
+    }
+
+    #[inline]
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.index.contains_key(key)
+    }
+
+    #[inline]
+    pub fn contains_value<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    #[inline] // None when the key is unknown, dead or holds no value
+    pub fn update_with<Q, G, R>(&mut self, key: &Q, f: G) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        G: FnOnce(&mut V) -> R,
+    {
+        self.get_mut(key).map(f)
+    }
+}
+
+impl<K, V, F> FromIterator<(K, V)> for FingerprintFrozenMap<K, V, F>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+    F: Fingerprint,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self::from_iter_last_wins(iter)
+    }
+}
+
+impl<K, V, F, S> From<HashMap<K, V, S>> for FingerprintFrozenMap<K, V, F>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+    F: Fingerprint,
+{
+    fn from(map: HashMap<K, V, S>) -> Self {
+        Self::from_unique_pairs(map)
+    }
+}
@@ -1,18 +1,21 @@
-use bitvec::bitvec;
-use ph::{
-    BuildDefaultSeededHasher,
-    phast::{
-        DefaultCompressedArray, Function2, Params, ShiftOnlyWrapped,
-        bits_per_seed_to_100_bucket_size,
-    },
-    seeds::BitsFast,
-};
-use std::{hash::Hash, mem::MaybeUninit};
+use bitvec::vec::BitVec;
+use std::{borrow::Borrow, collections::HashMap, hash::Hash, iter::Enumerate, time::Instant, vec};
 
 use crate::index::prelude::*;
+use crate::map::entry::Slot;
+use crate::map::{
+    BuildError, BuildStats, Entry, FrozenMapBuilder, FrozenMapError, MapStats, UpsertError,
+    UpsertReport,
+};
 use crate::store::prelude::*;
 
-//  SyncVerifiedFrozenMap    // higher overhead // no thread safe // key verification
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+// keys hashed and prefetched ahead of verification in get_many
+pub(crate) const PREFETCH_BATCH: usize = 16;
+
+// FrozenMap  // higher overhead // not thread safe, see SyncFrozenMap // key verification
 
 pub struct FrozenMap<K, V>
 where
@@ -21,244 +24,534 @@ where
 {
     index: VerifiedIndex<K>,
     store: Store<V>,
+    reap_policy: ReapPolicy,
+    build: BuildStats,
 }
 
-// only use if the key value pair indexes line up properly
 impl<K, V> FrozenMap<K, V>
 where
     K: Hash + Eq + Send + Sync + Clone + Default,
     V: Send + Sync + Clone + Default,
 {
+    constructors!(V);
+    mutations!(V);
 
+    pub(crate) fn from_slots<F>(keys: Vec<K>, value: F, builder: &FrozenMapBuilder) -> Self
+    where
+        F: FnMut(&K) -> Option<V>,
+    {
+        let started = Instant::now();
+        let (index, values) = builder.build_index(keys, value);
 
-    #[inline]
-    pub fn unsafe_init(keys: Vec<K>, values: Vec<V>) -> Self { // only use if the key value pair indexes line up properly
-        let index_map: Function2<
-            BitsFast,
-            ShiftOnlyWrapped<2>,
-            DefaultCompressedArray,
-            BuildDefaultSeededHasher,
-        > = Function2::with_slice_p_threads_hash_sc(
-            &keys,
-            &Params::new(BitsFast(10), bits_per_seed_to_100_bucket_size(8)),
-            std::thread::available_parallelism().map_or(1, |v| v.into()),
-            BuildDefaultSeededHasher::default(),
-            ShiftOnlyWrapped::<2>,
-        );
-
-        //let mut sorted_keys = vec![K::default(); keys.len()];
-        // note this is expensive to double allocate keys for no good reason aka allocating a default just know the type then we overwrite it which is slow
-
-        // Build keys vector
-        let mut sorted_keys: Vec<MaybeUninit<K>> = Vec::with_capacity(keys.len());
-        unsafe {
-            sorted_keys.set_len(keys.len());
+        Self {
+            index,
+            store: Store::from_slots(values),
+            reap_policy: ReapPolicy::default(),
+            build: BuildStats::since(started, builder),
         }
+    }
 
-        // build values vector
-        let mut sorted_values: Vec<MaybeUninit<V>> = Vec::with_capacity(keys.len()); // allocated memory for n elemens
-        unsafe {
-            sorted_values.set_len(keys.len());
-        } // changes the actual length of the vec to n length without any overhead
+    #[inline] // pairs keys and values by position, the lengths must match
+    pub fn try_init(keys: Vec<K>, values: Vec<V>) -> Result<Self, BuildError<K>> {
+        Self::try_init_with(keys, values, &FrozenMapBuilder::default())
+    }
 
-        let init_bloom = bitvec![1; keys.len()];
+    #[inline]
+    pub fn try_init_with(
+        keys: Vec<K>,
+        values: Vec<V>,
+        builder: &FrozenMapBuilder,
+    ) -> Result<Self, BuildError<K>> {
+        builder.check_lengths(keys.len(), values.len())?;
+        Self::try_from_pairs_with(keys.into_iter().zip(values).collect(), builder)
+    }
 
-        keys.into_iter().zip(values.into_iter()).for_each(|(key, val)| {
-            let idx = index_map.get(&key);
+    #[inline]
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.index.get_index(key)?;
 
-            sorted_keys[idx].write(key);
-            sorted_values[idx].write(val);
-        });
+        if self.index.keys.dead_key(idx) {
+            return None;
+        }
 
-        let frozen_index = VerifiedIndex {
-            mphf: index_map,
-            keys: WithKeys::new_from_uninit(sorted_keys),
-        };
+        if !self.index.keys.matches(idx, key) {
+            return None;
+        }
 
-        let store = Store::new(sorted_values, init_bloom);
+        self.store.get_value(idx)
+    }
 
-        Self {
-            index: frozen_index,
-            store,
+    #[inline]
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.index.get_index(key)?;
+
+        if self.index.keys.dead_key(idx) {
+            return None;
         }
-    }
 
+        if !self.index.keys.matches(idx, key) {
+            return None;
+        }
 
+        self.store.get_mut_value(idx)
+    }
 
+    // mutable access to several values at once, None if any key is unknown, dead,
+    // holds no value or is passed twice
+    pub fn get_many_mut<Q, const N: usize>(&mut self, keys: [&Q; N]) -> Option<[&mut V; N]>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut idxs = [0usize; N];
+
+        for (slot, key) in idxs.iter_mut().zip(keys) {
+            *slot = self.index.live_index(key).ok()?;
+        }
 
+        self.store.get_disjoint_mut(idxs)
+    }
 
+    // swaps the values (or the lack of one) held by two live keys
+    #[inline]
+    pub fn swap_values<Q>(&mut self, a: &Q, b: &Q) -> Result<(), FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let a = self.index.live_index(a)?;
+        let b = self.index.live_index(b)?;
+
+        self.store.swap(a, b);
+        Ok(())
+    }
 
+    // resolves a whole batch in chunks: hash every key of the chunk, prefetch its key and
+    // value slots, then verify, so the cache misses of a chunk overlap instead of chaining
+    pub fn get_many<Q>(&self, keys: &[Q]) -> Vec<Option<&V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        let mut out = Vec::with_capacity(keys.len());
+        let mut idxs = [0usize; PREFETCH_BATCH];
+
+        for chunk in keys.chunks(PREFETCH_BATCH) {
+            for (slot, key) in idxs.iter_mut().zip(chunk) {
+                let Some(idx) = self.index.get_index(key) else {
+                    return keys.iter().map(|_| None).collect(); // empty map
+                };
+
+                self.index.keys.prefetch(idx);
+                self.store.prefetch(idx);
+                *slot = idx;
+            }
+
+            out.extend(chunk.iter().zip(idxs).map(|(key, idx)| {
+                if self.index.keys.dead_key(idx) || !self.index.keys.matches(idx, key) {
+                    return None;
+                }
+
+                self.store.get_value(idx)
+            }));
+        }
 
-    #[inline] // encode the keys outside of this call idealy
-    pub fn from_vec(keys: Vec<K>) -> Self {
-        let index_map: Function2<
-            BitsFast,
-            ShiftOnlyWrapped<2>,
-            DefaultCompressedArray,
-            BuildDefaultSeededHasher,
-        > = Function2::with_slice_p_threads_hash_sc(
-            &keys,
-            &Params::new(BitsFast(10), bits_per_seed_to_100_bucket_size(8)),
-            std::thread::available_parallelism().map_or(1, |v| v.into()),
-            BuildDefaultSeededHasher::default(),
-            ShiftOnlyWrapped::<2>,
-        );
+        out
+    }
 
-        //let mut sorted_keys = vec![K::default(); keys.len()];
-        // note this is expensive to double allocate keys for no good reason aka allocating a default just know the type then we overwrite it which is slow
+    #[inline]
+    pub fn entry<Q>(&mut self, key: &Q) -> Entry<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let Some(idx) = self.index.get_index(key) else {
+            return Entry::Unknown;
+        };
 
-        // Build keys vector
-        let mut sorted_keys: Vec<MaybeUninit<K>> = Vec::with_capacity(keys.len());
-        unsafe {
-            sorted_keys.set_len(keys.len());
+        if !self.index.keys.matches(idx, key) {
+            return Entry::Unknown;
         }
 
-        // build values vector
-        let mut sorted_values: Vec<MaybeUninit<V>> = Vec::with_capacity(keys.len()); // allocated memory for n elemens
-        unsafe {
-            sorted_values.set_len(keys.len());
-        } // changes the actual length of the vec to n length without any overhead
+        let slot = Slot {
+            index: &mut self.index,
+            store: &mut self.store,
+            idx,
+            reap_policy: self.reap_policy,
+        };
 
-        let init_bloom = bitvec![0; keys.len()];
+        slot.into_entry()
+    }
 
-        keys.into_iter().for_each(|key| {
-            let idx = index_map.get(&key);
+    // handles skip the mphf and key compare on repeated lookups, a handle is only
+    // valid for the map it was taken from and goes stale once its key is reaped
+    #[inline]
+    pub fn handle<Q>(&self, key: &Q) -> Option<SlotHandle>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.index.get_index(key)?;
+
+        if self.index.keys.dead_key(idx) || !self.index.keys.matches(idx, key) {
+            return None;
+        }
 
-            sorted_keys[idx].write(key);
-        });
+        let idx32 = u32::try_from(idx).ok()?;
+        self.index.keys.track_generations();
 
-        let frozen_index = VerifiedIndex {
-            mphf: index_map,
-            keys: WithKeys::new_from_uninit(sorted_keys),
-        };
+        Some(SlotHandle {
+            idx: idx32,
+            generation: self.index.keys.generation(idx),
+        })
+    }
 
-        let store = Store::new(sorted_values, init_bloom);
+    #[inline]
+    pub fn get_by_handle(&self, handle: SlotHandle) -> Option<&V> {
+        let idx = self.resolve_handle(handle)?;
+        self.store.get_value(idx)
+    }
 
-        Self {
-            index: frozen_index,
-            store,
-        }
+    #[inline]
+    pub fn get_mut_by_handle(&mut self, handle: SlotHandle) -> Option<&mut V> {
+        let idx = self.resolve_handle(handle)?;
+        self.store.get_mut_value(idx)
     }
 
     #[inline]
-    pub fn get(&self, key: &K) -> Option<&V> {
-        let idx = self.index.get_index(key);
+    pub fn reap_by_handle(&mut self, handle: SlotHandle) -> Result<Option<V>, FrozenMapError> {
+        let idx = self
+            .resolve_handle(handle)
+            .ok_or(FrozenMapError::StaleHandle)?;
 
-        if self.index.keys.dead_key(idx) {
-            return None;
-        }
+        self.index.keys.kill(idx);
+        Ok(self.store.reap(idx, self.reap_policy))
+    }
+
+    #[inline]
+    fn resolve_handle(&self, handle: SlotHandle) -> Option<usize> {
+        let idx = handle.idx as usize;
 
-        if self.index.keys.get(idx) != key {
+        if idx >= self.index.keys.capacity()
+            || self.index.keys.generation(idx) != handle.generation
+            || self.index.keys.dead_key(idx)
+        {
             return None;
         }
 
-        self.store.get_value(idx)
+        Some(idx)
     }
 
     #[inline]
-    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
-        let idx = self.index.get_index(key);
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.index.contains_key(key)
+    }
 
-        if self.index.keys.dead_key(idx) {
-            return None;
+    #[inline] // live key holding a value, foreign and reaped keys never count
+    pub fn contains_value<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    #[inline] // None when the key is unknown, dead or holds no value
+    pub fn update_with<Q, F, R>(&mut self, key: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        F: FnOnce(&mut V) -> R,
+    {
+        self.get_mut(key).map(f)
+    }
+
+    // keeps the values of live keys f returns true for, the rest are dropped, keys stay live
+    #[inline]
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        for idx in 0..self.index.keys.capacity() {
+            if self.index.keys.dead_key(idx) {
+                continue;
+            }
+
+            if let Some(value) = self.store.get_mut_value(idx)
+                && !f(self.index.keys.get(idx), value)
+            {
+                self.store.remove_value(idx);
+            }
         }
+    }
 
-        if self.index.keys.get(idx) != key {
-            return None;
+    // reaps every live key f returns true for under the map's reap policy, ReturnValue
+    // keeps the values since there is no caller to hand them to, returns how many were reaped
+    #[inline]
+    pub fn reap_where<F>(&mut self, mut f: F) -> usize
+    where
+        F: FnMut(&K) -> bool,
+    {
+        let policy = self.reap_policy.without_return();
+        let mut reaped = 0;
+
+        for idx in 0..self.index.keys.capacity() {
+            if self.index.keys.dead_key(idx) || !f(self.index.keys.get(idx)) {
+                continue;
+            }
+
+            self.index.keys.kill(idx);
+            self.store.reap(idx, policy);
+            reaped += 1;
         }
 
-        self.store.get_mut_value(idx)
+        reaped
     }
 
+    #[inline] // memory per part and how the map was built
+    pub fn stats(&self) -> MapStats {
+        MapStats::new(&self.index, &self.store, self.build)
+    }
+
+    // all iterators borrow from the map in slot order, nothing is cloned,
+    // unless stated otherwise they skip reaped keys
 
     #[inline]
-    pub fn contains(&self, key: &K) -> bool {
-        self.index.contains_key(key)
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        let keys = &self.index.keys;
+
+        self.store
+            .iter()
+            .filter(move |(idx, _)| !keys.dead_key(*idx))
+            .map(move |(idx, v)| (keys.get(idx), v))
+    }
+
+    #[inline]
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&K, &mut V)> {
+        let keys = &self.index.keys;
+
+        self.store
+            .iter_mut()
+            .filter(move |(idx, _)| !keys.dead_key(*idx))
+            .map(move |(idx, v)| (keys.get(idx), v))
     }
 
     #[inline]
-    pub fn contains_value(&self, key: &K) -> bool {
-        let idx = self.index.get_index(key);
-        !self.store.get_value(idx).is_none()
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        let keys = &self.index.keys;
+
+        (0..keys.capacity())
+            .filter(move |idx| !keys.dead_key(*idx))
+            .map(move |idx| keys.get(idx))
+    }
+
+    #[inline] // every key even if it is reaped
+    pub fn iter_keys(&self) -> impl Iterator<Item = &K> {
+        self.index.keys.as_slice().iter()
     }
 
     #[inline]
-    pub fn upsert(&mut self, key: K, value: V) -> Result<(), &str> {
-        let idx = self.index.get_index(&key);
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
 
-        if self.index.keys.dead_key(idx) {
-            return Err("Dead key");
-        }
+    #[inline]
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.iter_mut().map(|(_, v)| v)
+    }
 
-        if self.index.keys.get(idx) == &key {
-            self.store.update(idx, value);
-            Ok(())
-        } else {
-            Err("Failed to upsert, key does not exist")
-        }
+    #[inline] // reaped keys, with the value they still hold if any
+    pub fn iter_dead(&self) -> impl Iterator<Item = (&K, Option<&V>)> {
+        let keys = &self.index.keys;
+
+        keys.dead_slots()
+            .map(move |idx| (keys.get(idx), self.store.get_value(idx)))
+    }
+
+    #[inline] // live keys without a value
+    pub fn iter_uninitialized(&self) -> impl Iterator<Item = &K> {
+        let keys = &self.index.keys;
+
+        (0..keys.capacity())
+            .filter(move |idx| !keys.dead_key(*idx) && self.store.get_value(*idx).is_none())
+            .map(move |idx| keys.get(idx))
     }
 
     #[inline]
-    pub fn drop_value(&mut self, key: &K) -> Result<(), &str> {
-        let idx = self.index.get_index(key);
-
-        if self.index.keys.get(idx) == key {
-            self.store.remove_value(idx);
-            Ok(())
-        } else {
-            Err("Failed to drop value, key does not exist")
-        }
+    pub fn iter_all_slots(&self) -> impl Iterator<Item = (&K, SlotState<'_, V>)> {
+        let keys = &self.index.keys;
+
+        (0..keys.capacity()).map(move |idx| {
+            let value = self.store.get_value(idx);
+
+            let state = match (keys.dead_key(idx), value) {
+                (true, value) => SlotState::Dead(value),
+                (false, Some(v)) => SlotState::Occupied(v),
+                (false, None) => SlotState::Vacant,
+            };
+
+            (keys.get(idx), state)
+        })
     }
+}
+
+// compact reference to a slot of a FrozenMap, see FrozenMap::handle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SlotHandle {
+    idx: u32,
+    generation: u32,
+}
+
+// state of a single slot as seen by iter_all_slots
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotState<'a, V> {
+    Occupied(&'a V),     // live key holding a value
+    Vacant,              // live key without a value
+    Dead(Option<&'a V>), // reaped key, the value is kept until dropped
+}
 
+// parallel iteration over slot ranges, dead keys and empty slots are skipped
+#[cfg(feature = "rayon")]
+impl<K, V> FrozenMap<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
     #[inline]
-    pub fn reap_key(&mut self, key: &K) -> Result<(), &str> {
-        let idx = self.index.get_index(key);
+    pub fn par_iter(&self) -> impl ParallelIterator<Item = (&K, &V)> {
+        let keys = &self.index.keys;
 
-        if self.index.keys.dead_key(idx) {
-            return Err("Key is already dead");
-        }
+        self.store
+            .par_iter()
+            .filter(move |(idx, _)| !keys.dead_key(*idx))
+            .map(move |(idx, v)| (keys.get(idx), v))
+    }
 
-        if self.index.keys.get(idx) == key {
-            self.index.keys.kill(idx);
-            Ok(())
-        } else {
-            Err("Failed to kill key, key does not exist")
-        }
+    #[inline]
+    pub fn par_values_mut(&mut self) -> impl ParallelIterator<Item = &mut V> {
+        let keys = &self.index.keys;
+
+        self.store
+            .par_iter_mut()
+            .filter(move |(idx, _)| !keys.dead_key(*idx))
+            .map(|(_, v)| v)
     }
 
     #[inline]
-    pub fn rehydrate_key(&mut self, key: &K) -> Result<(), &str> {
-        let idx = self.index.get_index(key);
+    pub fn par_for_each_live<F>(&mut self, f: F)
+    where
+        F: Fn(&K, &mut V) + Sync + Send,
+    {
+        let keys = &self.index.keys;
+
+        self.store
+            .par_iter_mut()
+            .filter(|(idx, _)| !keys.dead_key(*idx))
+            .for_each(|(idx, v)| f(keys.get(idx), v));
+    }
 
-        if !self.index.keys.dead_key(idx) {
-            return Err("Key is already alive");
-        }
+    // parallel retain, values of dead keys are left alone
+    #[inline]
+    pub fn par_retain<F>(&mut self, f: F)
+    where
+        F: Fn(&K, &mut V) -> bool + Sync,
+    {
+        let keys = &self.index.keys;
+
+        self.store
+            .par_retain(|idx, v| keys.dead_key(idx) || f(keys.get(idx), v));
+    }
+}
 
-        if self.index.keys.get(idx) == key {
-            self.index.keys.rehydrate(idx);
-            Ok(())
-        } else {
-            Err("Failed to kill key, key does not exist")
-        }
+impl<K, V> FrozenMap<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    #[inline]
+    pub(crate) fn into_parts(self) -> (VerifiedIndex<K>, Store<V>, ReapPolicy) {
+        (self.index, self.store, self.reap_policy)
     }
+}
+
+impl<K, V> IntoIterator for FrozenMap<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    type Item = (K, V);
+    type IntoIter = FrozenMapIntoIter<K, V>;
 
     #[inline]
-    pub fn len(&self) -> usize {
-        self.index.keys.len()
+    fn into_iter(self) -> Self::IntoIter {
+        let (keys, tombstone) = self.index.keys.into_parts();
+
+        FrozenMapIntoIter {
+            keys: keys.into_vec().into_iter().enumerate(),
+            tombstone,
+            values: self.store.into_values(),
+        }
     }
+}
+
+// consuming iterator, yields the live key value pairs, values of reaped keys are dropped
+pub struct FrozenMapIntoIter<K, V> {
+    keys: Enumerate<vec::IntoIter<K>>,
+    tombstone: BitVec,
+    values: IntoValues<V>,
+}
+
+impl<K, V> Iterator for FrozenMapIntoIter<K, V> {
+    type Item = (K, V);
 
     #[inline]
-    pub fn iter(&self) -> impl Iterator<Item = (K, V)> {
-        self.index
-            .keys
-            .get_keys()
-            .into_iter()
-            .zip(self.store.get_values())
-            .filter_map(|(k, v)| v.map(|v| (k, v)))
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (idx, v) = self.values.next()?;
+
+            if self.tombstone[idx] {
+                continue;
+            }
+
+            let (_, k) = self.keys.find(|(i, _)| *i == idx)?;
+            return Some((k, v));
+        }
     }
 
     #[inline]
-    pub fn iter_keys(&self) -> impl Iterator<Item = K> {
-        self.index.keys.get_keys().into_iter()
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.values.size_hint().1)
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for FrozenMap<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self::from_iter_last_wins(iter)
+    }
+}
+
+impl<K, V, S> From<HashMap<K, V, S>> for FrozenMap<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    fn from(map: HashMap<K, V, S>) -> Self {
+        Self::from_unique_pairs(map)
     }
 }
@@ -0,0 +1,337 @@
+use std::{borrow::Borrow, collections::HashMap, hash::Hash, mem::MaybeUninit};
+
+use crate::index::{place, prelude::*};
+use crate::map::{BuildError, FrozenMapBuilder, FrozenMapError, UpsertError};
+use crate::store::prelude::*;
+
+// FrozenMapAoS  // key and value share a slot // not thread safe // key verification
+//
+// FrozenMap keeps keys and values in separate arrays, so a verified hit touches two cache
+// lines far apart, here the key, the value and the slot flags sit next to each other and a
+// hit usually costs a single miss, at the price of scanning keys and values together
+//
+// pick per workload with `cargo bench --bench layout`
+
+const INIT: u8 = 1; // the slot holds a value
+const DEAD: u8 = 1 << 1; // the key is reaped
+
+struct AosSlot<K, V> {
+    key: K,
+    value: MaybeUninit<V>,
+    flags: u8,
+}
+
+impl<K, V> AosSlot<K, V> {
+    #[inline]
+    fn has(&self, flag: u8) -> bool {
+        self.flags & flag != 0
+    }
+
+    #[inline]
+    fn value(&self) -> Option<&V> {
+        self.has(INIT)
+            .then(|| unsafe { self.value.assume_init_ref() })
+    }
+
+    #[inline]
+    fn value_mut(&mut self) -> Option<&mut V> {
+        self.has(INIT)
+            .then(|| unsafe { self.value.assume_init_mut() })
+    }
+
+    #[inline]
+    fn take(&mut self) -> Option<V> {
+        if !self.has(INIT) {
+            return None;
+        }
+
+        self.flags &= !INIT;
+        Some(unsafe { self.value.assume_init_read() })
+    }
+
+    #[inline] // returns the replaced value
+    fn put(&mut self, value: V) -> Option<V> {
+        let old = self.take();
+
+        self.value.write(value);
+        self.flags |= INIT;
+        old
+    }
+}
+
+pub struct FrozenMapAoS<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    mphf: Mphf,
+    slots: Box<[AosSlot<K, V>]>,
+    len: usize,   // live keys
+    count: usize, // slots holding a value, live or dead
+    reap_policy: ReapPolicy,
+}
+
+impl<K, V> FrozenMapAoS<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    constructors!(V);
+
+    fn from_slots<F>(keys: Vec<K>, mut value: F, builder: &FrozenMapBuilder) -> Self
+    where
+        F: FnMut(&K) -> Option<V>,
+    {
+        let mphf = builder.build_mphf(&keys);
+        let n = keys.len();
+
+        let slots: Box<[AosSlot<K, V>]> = place(&mphf, keys, |key| match value(&key) {
+            Some(v) => AosSlot {
+                key,
+                value: MaybeUninit::new(v),
+                flags: INIT,
+            },
+            None => AosSlot {
+                key,
+                value: MaybeUninit::uninit(),
+                flags: 0,
+            },
+        })
+        .into_boxed_slice();
+
+        let count = slots.iter().filter(|slot| slot.flags & INIT != 0).count();
+
+        Self {
+            mphf,
+            slots,
+            len: n,
+            count,
+            reap_policy: ReapPolicy::default(),
+        }
+    }
+
+    #[inline] // None for keys outside the key set, dead keys still resolve
+    fn slot_index<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.slots.is_empty() {
+            return None; // no mphf to ask, see FrozenIndex::get_index
+        }
+
+        let idx = self.mphf.get(key);
+        (self.slots[idx].key.borrow() == key).then_some(idx)
+    }
+
+    #[inline]
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let slot = &self.slots[self.slot_index(key)?];
+
+        if slot.has(DEAD) {
+            return None;
+        }
+
+        slot.value()
+    }
+
+    #[inline]
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let slot = &mut self.slots[self.slot_index(key)?];
+
+        if slot.has(DEAD) {
+            return None;
+        }
+
+        slot.value_mut()
+    }
+
+    #[inline]
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.slot_index(key)
+            .is_some_and(|idx| !self.slots[idx].has(DEAD))
+    }
+
+    #[inline]
+    pub fn contains_value<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    #[inline]
+    pub fn upsert(&mut self, key: K, value: V) -> Result<(), UpsertError<K, V>> {
+        let error = match self.slot_index(&key) {
+            None => FrozenMapError::UnknownKey,
+            Some(idx) if self.slots[idx].has(DEAD) => FrozenMapError::DeadKey,
+            Some(idx) => {
+                if self.slots[idx].put(value).is_none() {
+                    self.count += 1;
+                }
+                return Ok(());
+            }
+        };
+
+        Err(UpsertError { error, key, value })
+    }
+
+    #[inline]
+    pub fn drop_value<Q>(&mut self, key: &Q) -> Result<(), FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.slot_index(key).ok_or(FrozenMapError::UnknownKey)?;
+
+        if self.slots[idx].take().is_some() {
+            self.count -= 1;
+        }
+        Ok(())
+    }
+
+    #[inline] // the value is kept, dropped or returned depending on the reap policy
+    pub fn reap_key<Q>(&mut self, key: &Q) -> Result<Option<V>, FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.slot_index(key).ok_or(FrozenMapError::UnknownKey)?;
+        let slot = &mut self.slots[idx];
+
+        if slot.has(DEAD) {
+            return Err(FrozenMapError::AlreadyDead);
+        }
+
+        slot.flags |= DEAD;
+        self.len -= 1;
+
+        let value = match self.reap_policy {
+            ReapPolicy::KeepValue => return Ok(None),
+            ReapPolicy::DropValue | ReapPolicy::ReturnValue => slot.take(),
+        };
+
+        if value.is_some() {
+            self.count -= 1;
+        }
+
+        Ok(value.filter(|_| self.reap_policy == ReapPolicy::ReturnValue))
+    }
+
+    #[inline]
+    pub fn rehydrate_key<Q>(&mut self, key: &Q) -> Result<(), FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.slot_index(key).ok_or(FrozenMapError::UnknownKey)?;
+        let slot = &mut self.slots[idx];
+
+        if !slot.has(DEAD) {
+            return Err(FrozenMapError::AlreadyAlive);
+        }
+
+        slot.flags &= !DEAD;
+        self.len += 1;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // slots, live and dead
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    #[inline]
+    pub fn dead_count(&self) -> usize {
+        self.slots.len() - self.len
+    }
+
+    // values held, including the ones kept by reaped keys
+    #[inline]
+    pub fn value_count(&self) -> usize {
+        self.count
+    }
+
+    #[inline]
+    pub fn reap_policy(&self) -> ReapPolicy {
+        self.reap_policy
+    }
+
+    #[inline]
+    pub fn set_reap_policy(&mut self, reap_policy: ReapPolicy) {
+        self.reap_policy = reap_policy;
+    }
+
+    // slot order, skips reaped keys and empty slots
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.slots
+            .iter()
+            .filter(|slot| slot.flags == INIT)
+            .map(|slot| (&slot.key, unsafe { slot.value.assume_init_ref() }))
+    }
+
+    #[inline]
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&K, &mut V)> {
+        self.slots
+            .iter_mut()
+            .filter(|slot| slot.flags == INIT)
+            .map(|slot| (&slot.key, unsafe { slot.value.assume_init_mut() }))
+    }
+}
+
+impl<K, V> Drop for FrozenMapAoS<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    fn drop(&mut self) {
+        self.slots.iter_mut().for_each(|slot| {
+            slot.take();
+        });
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for FrozenMapAoS<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self::from_iter_last_wins(iter)
+    }
+}
+
+impl<K, V, S> From<HashMap<K, V, S>> for FrozenMapAoS<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    fn from(map: HashMap<K, V, S>) -> Self {
+        Self::from_unique_pairs(map)
+    }
+}
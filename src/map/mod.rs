@@ -1,5 +1,34 @@
+#[macro_use]
+mod constructors;
+#[macro_use]
+mod mutations;
+
+mod arc_frozen_map;
+mod atomic_frozen_map;
+mod builder;
+mod entry;
+mod error;
+mod fingerprint_frozen_map;
 mod frozen_map;
+mod frozen_map_aos;
+mod sharded_frozen_map;
+mod snapshot_frozen_map;
+mod stats;
+mod sync_frozen_map;
 mod unsafe_frozen_map;
 
+pub use arc_frozen_map::*;
+pub use atomic_frozen_map::*;
+pub use builder::*;
+pub use entry::*;
+pub use error::*;
+pub use fingerprint_frozen_map::*;
 pub use frozen_map::*;
+pub use frozen_map_aos::*;
+pub use sharded_frozen_map::*;
+pub use snapshot_frozen_map::*;
+pub use stats::*;
+pub use sync_frozen_map::*;
 pub use unsafe_frozen_map::*;
+
+pub use crate::store::ReapPolicy;
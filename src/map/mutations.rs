@@ -0,0 +1,232 @@
+// The write and bookkeeping set the single threaded maps share (FrozenMap, UnsafeFrozenMap,
+// FingerprintFrozenMap), expanded inside the map's impl block over its index, store and
+// reap_policy fields, keys are verified as far as the index can, see VerifyKey
+
+macro_rules! mutations {
+    ($value:ty) => {
+        #[inline]
+        pub fn upsert(&mut self, key: K, value: $value) -> Result<(), UpsertError<K, $value>> {
+            match self.index.live_index(&key) {
+                Ok(idx) => {
+                    self.store.update(idx, value);
+                    Ok(())
+                }
+                Err(error) => Err(UpsertError { error, key, value }),
+            }
+        }
+
+        // writes every valid pair of the batch, duplicates in the batch are applied in order
+        pub fn upsert_many<I>(&mut self, pairs: I) -> UpsertReport<K, $value>
+        where
+            I: IntoIterator<Item = (K, $value)>,
+        {
+            let mut report = UpsertReport::default();
+
+            for (key, value) in pairs {
+                match self.index.live_index(&key) {
+                    Ok(idx) => report.write(key, self.store.replace(idx, value)),
+                    Err(error) => report.reject(error, key, value),
+                }
+            }
+
+            report
+        }
+
+        // all or nothing upsert_many, a batch with any unknown or dead key writes nothing and the
+        // report hands every pair back, the offending ones as unknown or dead, the rest untouched
+        pub fn try_upsert_many<I>(
+            &mut self,
+            pairs: I,
+        ) -> Result<UpsertReport<K, $value>, UpsertReport<K, $value>>
+        where
+            I: IntoIterator<Item = (K, $value)>,
+        {
+            let pairs: Vec<(K, $value)> = pairs.into_iter().collect();
+            let slots: Vec<Result<usize, FrozenMapError>> = pairs
+                .iter()
+                .map(|(key, _)| self.index.live_index(key))
+                .collect();
+
+            let mut report = UpsertReport::default();
+
+            if slots.iter().all(Result::is_ok) {
+                for ((key, value), idx) in pairs.into_iter().zip(slots.into_iter().flatten()) {
+                    report.write(key, self.store.replace(idx, value));
+                }
+
+                return Ok(report);
+            }
+
+            for ((key, value), slot) in pairs.into_iter().zip(slots) {
+                match slot {
+                    Ok(_) => report.hand_back(key, value),
+                    Err(error) => report.reject(error, key, value),
+                }
+            }
+
+            Err(report)
+        }
+
+        #[inline] // upsert that hands back the replaced value
+        pub fn replace(
+            &mut self,
+            key: K,
+            value: $value,
+        ) -> Result<Option<$value>, UpsertError<K, $value>> {
+            match self.index.live_index(&key) {
+                Ok(idx) => Ok(self.store.replace(idx, value)),
+                Err(error) => Err(UpsertError { error, key, value }),
+            }
+        }
+
+        #[inline] // moves the value out of a live key, the key stays live
+        pub fn take<Q>(&mut self, key: &Q) -> Option<$value>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            let idx = self.index.live_index(key).ok()?;
+            self.store.take(idx)
+        }
+
+        // only writes to a live key without a value, anything else hands key and value back
+        #[inline]
+        pub fn insert_if_absent(
+            &mut self,
+            key: K,
+            value: $value,
+        ) -> Result<&mut $value, UpsertError<K, $value>> {
+            let error = match self.index.live_index(&key) {
+                Ok(idx) if self.store.get_value(idx).is_none() => {
+                    return Ok(self.store.insert(idx, value));
+                }
+                Ok(_) => FrozenMapError::Occupied,
+                Err(error) => error,
+            };
+
+            Err(UpsertError { error, key, value })
+        }
+
+        #[inline] // a reaped key drops the value it kept
+        pub fn drop_value<Q>(&mut self, key: &Q) -> Result<(), FrozenMapError>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            let idx = self.index.known_index(key)?;
+
+            if self.index.keys.dead_key(idx) {
+                self.store.remove_dead_value(idx);
+            } else {
+                self.store.remove_value(idx);
+            }
+            Ok(())
+        }
+
+        #[inline]
+        pub fn reap_key<Q>(&mut self, key: &Q) -> Result<Option<$value>, FrozenMapError>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            let idx = self.index.known_index(key)?;
+
+            if self.index.keys.dead_key(idx) {
+                return Err(FrozenMapError::AlreadyDead);
+            }
+
+            self.index.keys.kill(idx);
+            Ok(self.store.reap(idx, self.reap_policy))
+        }
+
+        #[inline]
+        pub fn rehydrate_key<Q>(&mut self, key: &Q) -> Result<(), FrozenMapError>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            let idx = self.index.known_index(key)?;
+
+            if !self.index.keys.dead_key(idx) {
+                return Err(FrozenMapError::AlreadyAlive);
+            }
+
+            self.index.keys.rehydrate(idx);
+            self.store.rehydrate(idx);
+            Ok(())
+        }
+
+        #[inline]
+        pub fn len(&self) -> usize {
+            self.index.keys.len()
+        }
+
+        #[inline]
+        pub fn is_empty(&self) -> bool {
+            self.index.keys.is_empty()
+        }
+
+        #[inline]
+        pub fn reap_policy(&self) -> ReapPolicy {
+            self.reap_policy
+        }
+
+        #[inline]
+        pub fn set_reap_policy(&mut self, reap_policy: ReapPolicy) {
+            self.reap_policy = reap_policy;
+        }
+
+        // drops every value still held by a reaped key, returns how many were dropped
+        #[inline]
+        pub fn purge_dead_values(&mut self) -> usize {
+            self.store.purge_dead(self.index.keys.dead_slots())
+        }
+
+        // revives every reaped key, values kept by them come back too, returns how many were revived
+        #[inline]
+        pub fn rehydrate_all(&mut self) -> usize {
+            let revived = self.index.keys.dead_count();
+
+            self.index.keys.rehydrate_all();
+            self.store.rehydrate_all();
+            revived
+        }
+
+        #[inline] // drops every value, live and dead, keys stay as they are
+        pub fn clear_values(&mut self) {
+            self.store.clear();
+        }
+
+        #[inline] // moves every value out in slot order, live and dead
+        pub fn drain_values(&mut self) -> Drain<'_, $value> {
+            self.store.drain()
+        }
+
+        // slots, live and dead
+        #[inline]
+        pub fn capacity(&self) -> usize {
+            self.index.keys.capacity()
+        }
+
+        #[inline] // same as len
+        pub fn live_len(&self) -> usize {
+            self.index.keys.len()
+        }
+
+        #[inline]
+        pub fn dead_count(&self) -> usize {
+            self.index.keys.dead_count()
+        }
+
+        // values held, including the ones kept by reaped keys
+        #[inline]
+        pub fn value_count(&self) -> usize {
+            self.store.value_count()
+        }
+
+        #[inline]
+        pub fn live_value_count(&self) -> usize {
+            self.store.live_value_count()
+        }
+    };
+}
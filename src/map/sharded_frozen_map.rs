@@ -0,0 +1,344 @@
+use ph::BuildSeededHasher;
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    hash::Hash,
+    sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    thread,
+};
+
+use crate::index::prelude::*;
+use crate::map::{BuildError, FrozenMap, FrozenMapBuilder, FrozenMapError, UpsertError};
+use crate::store::prelude::*;
+
+// ShardedFrozenMap  // lock per shard // thread safe through &self // key verification
+//
+// the key set is split by a seeded hash into independently built FrozenMaps, every shard
+// sits behind its own RwLock so writers to different shards never wait on each other
+//
+// shards are built in parallel, the builder threads are divided between them, an empty
+// shard has no usable mphf so its keys are rejected before the lock is taken
+//
+// the shard count is given to the _with constructors and clamped to the key count, 0 gives
+// one shard per build thread but no more than one per AUTO_SHARD_KEYS keys
+
+// level seed for shard routing, kept apart from the mphf and fingerprint seeds
+const SHARD_SEED: u64 = 0xD1B5_4A32_D192_ED03;
+
+// keys per shard before an automatic shard count adds another shard
+const AUTO_SHARD_KEYS: usize = 4096;
+
+struct Shard<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    map: RwLock<FrozenMap<K, V>>,
+    capacity: usize,
+}
+
+pub struct ShardedFrozenMap<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    shards: Box<[Shard<K, V>]>,
+    hasher: SeededHasher,
+    reap_policy: ReapPolicy, // mirrored into every shard
+}
+
+impl<K, V> ShardedFrozenMap<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    constructors!(V, shards = 0);
+
+    // duplicates are checked over the whole key set before this, a key lands in exactly
+    // one shard so every shard build sees unique keys, value runs on the calling thread
+    // while the keys are split
+    fn from_slots<F>(keys: Vec<K>, mut value: F, shards: usize, builder: &FrozenMapBuilder) -> Self
+    where
+        F: FnMut(&K) -> Option<V>,
+    {
+        let shard_count = match shards {
+            0 => builder
+                .get_threads()
+                .min(keys.len().div_ceil(AUTO_SHARD_KEYS)),
+            shards => shards,
+        }
+        .clamp(1, keys.len().max(1));
+
+        let hasher = SeededHasher::new(builder.get_seed());
+
+        let mut parts: Vec<(Vec<K>, Vec<Option<V>>)> =
+            (0..shard_count).map(|_| (Vec::new(), Vec::new())).collect();
+
+        for key in keys {
+            let (keys, values) = &mut parts[shard_of(&hasher, &key, shard_count)];
+            values.push(value(&key));
+            keys.push(key);
+        }
+
+        let shard_builder = builder.threads(builder.get_threads() / shard_count);
+
+        let shards = thread::scope(|scope| {
+            let handles: Vec<_> = parts
+                .into_iter()
+                .map(|(keys, values)| {
+                    scope.spawn(move || {
+                        let capacity = keys.len();
+                        let mut values = values.into_iter();
+                        let map = FrozenMap::from_slots(
+                            keys,
+                            |_| values.next().flatten(),
+                            &shard_builder,
+                        );
+
+                        (map, capacity)
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        Self {
+            shards: shards
+                .into_iter()
+                .map(|(map, capacity)| Shard {
+                    map: RwLock::new(map),
+                    capacity,
+                })
+                .collect(),
+            hasher,
+            reap_policy: ReapPolicy::default(),
+        }
+    }
+
+    #[inline] // None for keys routed to an empty shard, those can't be part of the map
+    fn shard<Q>(&self, key: &Q) -> Option<&Shard<K, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let shard = &self.shards[shard_of(&self.hasher, key, self.shards.len())];
+        (shard.capacity > 0).then_some(shard)
+    }
+
+    #[inline]
+    fn read<Q>(&self, key: &Q) -> Option<RwLockReadGuard<'_, FrozenMap<K, V>>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let shard = self.shard(key)?;
+        Some(shard.map.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    #[inline]
+    fn write<Q>(&self, key: &Q) -> Option<RwLockWriteGuard<'_, FrozenMap<K, V>>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let shard = self.shard(key)?;
+        Some(shard.map.write().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    #[inline]
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.with_value(key, V::clone)
+    }
+
+    #[inline] // f runs under the read lock of the shard
+    pub fn with_value<Q, F, R>(&self, key: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        F: FnOnce(&V) -> R,
+    {
+        self.read(key)?.get(key).map(f)
+    }
+
+    #[inline] // f runs under the write lock of the shard
+    pub fn with_value_mut<Q, F, R>(&self, key: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        F: FnOnce(&mut V) -> R,
+    {
+        self.write(key)?.get_mut(key).map(f)
+    }
+
+    #[inline]
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.read(key).is_some_and(|shard| shard.contains(key))
+    }
+
+    #[inline]
+    pub fn contains_value<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.read(key)
+            .is_some_and(|shard| shard.contains_value(key))
+    }
+
+    #[inline]
+    pub fn upsert(&self, key: K, value: V) -> Result<(), UpsertError<K, V>> {
+        match self.write(&key) {
+            Some(mut shard) => shard.upsert(key, value),
+            None => Err(UpsertError {
+                error: FrozenMapError::UnknownKey,
+                key,
+                value,
+            }),
+        }
+    }
+
+    #[inline]
+    pub fn drop_value<Q>(&self, key: &Q) -> Result<(), FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.write(key)
+            .ok_or(FrozenMapError::UnknownKey)?
+            .drop_value(key)
+    }
+
+    #[inline] // the value is kept, dropped or returned depending on the reap policy
+    pub fn reap_key<Q>(&self, key: &Q) -> Result<Option<V>, FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.write(key)
+            .ok_or(FrozenMapError::UnknownKey)?
+            .reap_key(key)
+    }
+
+    #[inline]
+    pub fn rehydrate_key<Q>(&self, key: &Q) -> Result<(), FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.write(key)
+            .ok_or(FrozenMapError::UnknownKey)?
+            .rehydrate_key(key)
+    }
+
+    // live pairs cloned out one shard at a time under its read lock, the lock is released
+    // before the shard's pairs are yielded, so writes to other shards may land in between
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (K, V)> + '_ {
+        self.shards.iter().flat_map(|shard| {
+            let map = shard.map.read().unwrap_or_else(PoisonError::into_inner);
+            let pairs: Vec<(K, V)> = map.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+            pairs
+        })
+    }
+
+    // visits one shard at a time under its read lock without cloning, writers only ever wait
+    // on that shard, f must not call back into the map
+    pub fn for_each<F>(&self, mut f: F)
+    where
+        F: FnMut(&K, &V),
+    {
+        for shard in self.shards.iter() {
+            let map = shard.map.read().unwrap_or_else(PoisonError::into_inner);
+            map.iter().for_each(|(k, v)| f(k, v));
+        }
+    }
+
+    #[inline] // sums the shards one lock at a time, a snapshot while other threads reap
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| {
+                shard
+                    .map
+                    .read()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .len()
+            })
+            .sum()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // slots, live and dead
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.shards.iter().map(|shard| shard.capacity).sum()
+    }
+
+    #[inline]
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    #[inline]
+    pub fn reap_policy(&self) -> ReapPolicy {
+        self.reap_policy
+    }
+
+    #[inline]
+    pub fn set_reap_policy(&mut self, reap_policy: ReapPolicy) {
+        self.reap_policy = reap_policy;
+
+        for shard in self.shards.iter_mut() {
+            shard
+                .map
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner)
+                .set_reap_policy(reap_policy);
+        }
+    }
+}
+
+#[inline]
+fn shard_of<Q>(hasher: &SeededHasher, key: &Q, shards: usize) -> usize
+where
+    Q: Hash + ?Sized,
+{
+    // multiply shift maps the whole hash range onto the shards without a modulo
+    ((hasher.hash_one(key, SHARD_SEED) as u128 * shards as u128) >> 64) as usize
+}
+
+impl<K, V> FromIterator<(K, V)> for ShardedFrozenMap<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self::from_iter_last_wins(iter)
+    }
+}
+
+impl<K, V, S> From<HashMap<K, V, S>> for ShardedFrozenMap<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    fn from(map: HashMap<K, V, S>) -> Self {
+        Self::from_unique_pairs(map)
+    }
+}
@@ -0,0 +1,554 @@
+use std::{
+    borrow::Borrow,
+    cell::UnsafeCell,
+    hash::Hash,
+    sync::{
+        Arc, Mutex, PoisonError, Weak,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use bitvec::{bitvec, vec::BitVec};
+
+use crate::index::prelude::*;
+use crate::map::{FrozenMap, FrozenMapError, UpsertError};
+use crate::store::prelude::*;
+
+// Left-right snapshots of a FrozenMap  // wait free reads // single writer // key verification
+//
+// the index is shared and never written again, the values and tombstones are kept twice:
+// readers use the published side while the writer mutates the other one and logs every
+// operation, publish flips the sides, waits for the readers still on the old side to leave
+// it, then replays the log onto it so both sides match again
+//
+// a reader publishes which side its snapshot is on, tagged with a per reader snapshot count,
+// and clears it when the snapshot is dropped, the writer only waits on readers that were on
+// the old side when it flipped, so keep snapshots short
+//
+// a leaked snapshot can't be read again, it holds publish up until its reader takes another
+// snapshot or is dropped, try_publish gives up instead of waiting past a timeout, the flip has
+// happened by then so its writes are visible, the old side is caught up by the next publish
+// or try_publish, or by the next write, which waits for it like publish does
+
+const IDLE: usize = 0;
+
+#[inline] // snapshot count in the high bits, side + 1 in the low two
+fn reading(seq: usize, side: usize) -> usize {
+    (seq << 2) | (side + 1)
+}
+
+#[inline]
+fn reads_side(state: usize, side: usize) -> bool {
+    state & 0b11 == side + 1
+}
+
+struct Side<V>
+where
+    V: Send + Sync + Clone + Default,
+{
+    store: Store<V>,
+    tombstone: BitVec,
+    len: usize,
+}
+
+impl<V> Side<V>
+where
+    V: Send + Sync + Clone + Default,
+{
+    #[inline]
+    fn apply(&mut self, op: Op<V>) {
+        match op {
+            Op::Upsert(idx, value) => {
+                self.store.update(idx, value);
+            }
+            Op::DropValue(idx) => {
+                if self.tombstone[idx] {
+                    self.store.remove_dead_value(idx);
+                } else {
+                    self.store.remove_value(idx);
+                }
+            }
+            Op::Reap(idx, policy) => {
+                self.tombstone.set(idx, true);
+                self.len -= 1;
+                self.store.reap(idx, policy);
+            }
+            Op::Rehydrate(idx) => {
+                self.tombstone.set(idx, false);
+                self.len += 1;
+                self.store.rehydrate(idx);
+            }
+        }
+    }
+}
+
+// writer operations by slot, replayed onto the other side without hashing again
+#[derive(Clone)]
+enum Op<V> {
+    Upsert(usize, V),
+    DropValue(usize),
+    Reap(usize, ReapPolicy),
+    Rehydrate(usize),
+}
+
+struct Shared<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    index: VerifiedIndex<K>, // its own tombstone is left as built, the sides hold the live one
+    sides: [UnsafeCell<Side<V>>; 2],
+    published: AtomicUsize,
+    readers: Mutex<Vec<Weak<AtomicUsize>>>, // reader states, IDLE or reading(seq, side)
+}
+
+// the published side is only read, the other one is only touched by the single writer once
+// no reader is left on it
+unsafe impl<K, V> Sync for Shared<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+}
+
+pub struct SnapshotWriter<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    shared: Arc<Shared<K, V>>,
+    log: Vec<Op<V>>,
+    reap_policy: ReapPolicy,
+    retiring: Option<usize>, // side flipped away from, the log is replayed once it's free
+}
+
+impl<K, V> SnapshotWriter<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    // the map's reap policy carries over
+    pub fn new(map: FrozenMap<K, V>) -> Self {
+        let (index, store, reap_policy) = map.into_parts();
+
+        let mut tombstone = bitvec![0; index.keys.capacity()];
+        index
+            .keys
+            .dead_slots()
+            .for_each(|idx| tombstone.set(idx, true));
+
+        let side = || Side {
+            store: store.clone(),
+            tombstone: tombstone.clone(),
+            len: index.keys.len(),
+        };
+
+        let sides = [UnsafeCell::new(side()), UnsafeCell::new(side())];
+
+        Self {
+            shared: Arc::new(Shared {
+                index,
+                sides,
+                published: AtomicUsize::new(0),
+                readers: Mutex::new(Vec::new()),
+            }),
+            log: Vec::new(),
+            reap_policy,
+            retiring: None,
+        }
+    }
+
+    #[inline]
+    pub fn reader(&self) -> SnapshotReader<K, V> {
+        SnapshotReader::register(&self.shared)
+    }
+
+    #[inline] // the published side while the old one still has readers, it holds every write
+    fn side(&self) -> &Side<V> {
+        let idx = match self.retiring {
+            Some(old) => 1 - old,
+            None => 1 - self.shared.published.load(Ordering::SeqCst),
+        };
+
+        unsafe { &*self.shared.sides[idx].get() }
+    }
+
+    #[inline] // waits for the old side first if a try_publish timed out
+    fn side_mut(&mut self) -> &mut Side<V> {
+        self.settle(None);
+
+        let idx = 1 - self.shared.published.load(Ordering::SeqCst);
+        unsafe { &mut *self.shared.sides[idx].get() }
+    }
+
+    #[inline]
+    fn slot_index<Q>(&self, key: &Q) -> Result<usize, FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self
+            .shared
+            .index
+            .get_index(key)
+            .ok_or(FrozenMapError::UnknownKey)?;
+
+        if !self.shared.index.keys.matches(idx, key) {
+            return Err(FrozenMapError::UnknownKey);
+        }
+
+        Ok(idx)
+    }
+
+    #[inline] // the writer's view, unpublished writes included
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.slot_index(key).ok()?;
+        let side = self.side();
+
+        if side.tombstone[idx] {
+            return None;
+        }
+
+        side.store.get_value(idx)
+    }
+
+    #[inline]
+    pub fn upsert(&mut self, key: K, value: V) -> Result<(), UpsertError<K, V>> {
+        let error = match self.slot_index(&key) {
+            Ok(idx) if !self.side().tombstone[idx] => {
+                self.record(Op::Upsert(idx, value));
+                return Ok(());
+            }
+            Ok(_) => FrozenMapError::DeadKey,
+            Err(error) => error,
+        };
+
+        Err(UpsertError { error, key, value })
+    }
+
+    #[inline]
+    pub fn drop_value<Q>(&mut self, key: &Q) -> Result<(), FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.slot_index(key)?;
+
+        self.record(Op::DropValue(idx));
+        Ok(())
+    }
+
+    #[inline] // the value is kept, dropped or returned depending on the reap policy
+    pub fn reap_key<Q>(&mut self, key: &Q) -> Result<Option<V>, FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.slot_index(key)?;
+
+        if self.side().tombstone[idx] {
+            return Err(FrozenMapError::AlreadyDead);
+        }
+
+        let policy = self.reap_policy;
+        let side = self.side_mut();
+
+        side.tombstone.set(idx, true);
+        side.len -= 1;
+        let value = side.store.reap(idx, policy);
+
+        // a value handed back on this side is dropped on the other one
+        let replay = match policy {
+            ReapPolicy::KeepValue => ReapPolicy::KeepValue,
+            _ => ReapPolicy::DropValue,
+        };
+
+        self.log.push(Op::Reap(idx, replay));
+        Ok(value)
+    }
+
+    #[inline]
+    pub fn rehydrate_key<Q>(&mut self, key: &Q) -> Result<(), FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.slot_index(key)?;
+
+        if !self.side().tombstone[idx] {
+            return Err(FrozenMapError::AlreadyAlive);
+        }
+
+        self.record(Op::Rehydrate(idx));
+        Ok(())
+    }
+
+    #[inline] // applies the operation to the writer's side and logs it for the other one
+    fn record(&mut self, op: Op<V>) {
+        self.side_mut().apply(op.clone());
+        self.log.push(op);
+    }
+
+    #[inline] // operations not yet visible to readers
+    pub fn pending(&self) -> usize {
+        match self.retiring {
+            Some(_) => 0,
+            None => self.log.len(),
+        }
+    }
+
+    // makes every write so far visible to readers, blocks until the readers that were inside
+    // a snapshot of the previous side have left it
+    pub fn publish(&mut self) {
+        self.settle(None);
+
+        if !self.log.is_empty() {
+            self.flip();
+            self.settle(None);
+        }
+    }
+
+    // publish that waits at most timeout for the snapshots open on the old side, false when
+    // one outlives it, a leaked one always does, the writes are visible either way
+    pub fn try_publish(&mut self, timeout: Duration) -> bool {
+        let deadline = Some(Instant::now() + timeout);
+
+        if !self.settle(deadline) {
+            return false;
+        }
+
+        if !self.log.is_empty() {
+            self.flip();
+        }
+
+        self.settle(deadline)
+    }
+
+    #[inline]
+    fn flip(&mut self) {
+        let old = self.shared.published.load(Ordering::SeqCst);
+        self.shared.published.store(1 - old, Ordering::SeqCst);
+        self.retiring = Some(old);
+    }
+
+    // replays the log onto the retiring side once its readers are gone, false when the
+    // deadline passes first and the side stays retiring
+    fn settle(&mut self, deadline: Option<Instant>) -> bool {
+        let Some(old) = self.retiring else {
+            return true;
+        };
+
+        if !self.wait_for_readers(old, deadline) {
+            return false;
+        }
+
+        let side = unsafe { &mut *self.shared.sides[old].get() };
+        self.log.drain(..).for_each(|op| side.apply(op));
+        self.retiring = None;
+        true
+    }
+
+    // waits for every snapshot open on the side to be dropped, a reader that took a newer
+    // snapshot or was dropped itself is done with it, false once the deadline passes
+    //
+    // the registry lock is only held to collect the readers, a reader thread may register a
+    // new one while its snapshot is still open
+    fn wait_for_readers(&self, side: usize, deadline: Option<Instant>) -> bool {
+        let readers: Vec<(Weak<AtomicUsize>, usize)> = {
+            let mut readers = self
+                .shared
+                .readers
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+
+            readers.retain(|state| state.strong_count() > 0); // dropped readers
+
+            readers
+                .iter()
+                .filter_map(|state| {
+                    let seen = state.upgrade()?.load(Ordering::SeqCst);
+                    reads_side(seen, side).then(|| (state.clone(), seen))
+                })
+                .collect()
+        };
+
+        for (state, seen) in readers {
+            while state
+                .upgrade()
+                .is_some_and(|state| state.load(Ordering::SeqCst) == seen)
+            {
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    return false;
+                }
+
+                std::thread::yield_now();
+            }
+        }
+
+        true
+    }
+}
+
+impl<K, V> From<FrozenMap<K, V>> for SnapshotWriter<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    fn from(map: FrozenMap<K, V>) -> Self {
+        Self::new(map)
+    }
+}
+
+// one handle per reader thread, clone it to get another one
+pub struct SnapshotReader<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    shared: Arc<Shared<K, V>>,
+    state: Arc<AtomicUsize>,
+    seq: usize, // snapshots taken, tells a new snapshot apart from a leaked one
+}
+
+impl<K, V> SnapshotReader<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    fn register(shared: &Arc<Shared<K, V>>) -> Self {
+        let state = Arc::new(AtomicUsize::new(IDLE));
+
+        shared
+            .readers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Arc::downgrade(&state));
+
+        Self {
+            shared: Arc::clone(shared),
+            state,
+            seq: 0,
+        }
+    }
+
+    // never blocks, the writer's publish waits for the snapshot to be dropped
+    //
+    // the side is read again after announcing it, a flip in between means the writer may
+    // have missed the announcement, so the reader moves to the new side instead
+    #[inline]
+    pub fn snapshot(&mut self) -> Snapshot<'_, K, V> {
+        self.seq = self.seq.wrapping_add(1);
+
+        let idx = loop {
+            let idx = self.shared.published.load(Ordering::SeqCst);
+            self.state.store(reading(self.seq, idx), Ordering::SeqCst);
+
+            if self.shared.published.load(Ordering::SeqCst) == idx {
+                break idx;
+            }
+        };
+
+        let side = unsafe { &*self.shared.sides[idx].get() };
+
+        Snapshot {
+            index: &self.shared.index,
+            side,
+            state: &self.state,
+        }
+    }
+}
+
+impl<K, V> Clone for SnapshotReader<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    fn clone(&self) -> Self {
+        Self::register(&self.shared)
+    }
+}
+
+// consistent view of the values and tombstones as of the last publish
+pub struct Snapshot<'a, K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    index: &'a VerifiedIndex<K>,
+    side: &'a Side<V>,
+    state: &'a AtomicUsize,
+}
+
+impl<K, V> Snapshot<'_, K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    #[inline]
+    fn live_index<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.index.get_index(key)?;
+
+        if self.side.tombstone[idx] || !self.index.keys.matches(idx, key) {
+            return None;
+        }
+
+        Some(idx)
+    }
+
+    #[inline]
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.live_index(key)?;
+        self.side.store.get_value(idx)
+    }
+
+    #[inline]
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.live_index(key).is_some()
+    }
+
+    #[inline] // live keys holding a value
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.side
+            .store
+            .iter()
+            .filter(|(idx, _)| !self.side.tombstone[*idx])
+            .map(|(idx, value)| (self.index.keys.get(idx), value))
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.side.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.side.len == 0
+    }
+}
+
+impl<K, V> Drop for Snapshot<'_, K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    fn drop(&mut self) {
+        self.state.store(IDLE, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,86 @@
+use ph::GetSize;
+use std::{
+    hash::Hash,
+    time::{Duration, Instant},
+};
+
+use crate::index::{FrozenIndex, KeyStorage};
+use crate::map::FrozenMapBuilder;
+use crate::store::prelude::*;
+
+// Memory and build report of a map, byte counts are the inline size of every part,
+// heap memory owned by keys or values (String, Vec, ...) is not followed
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MapStats {
+    pub capacity: usize,
+    pub mphf_bytes: usize,
+    pub key_bytes: usize, // 0 for maps that don't store keys
+    pub value_bytes: usize,
+    pub init_bytes: usize,
+    pub tombstone_bytes: usize,
+    pub generation_bytes: usize, // 0 until a key is reaped after FrozenMap::handle was used
+    pub bits_per_key: f64,       // mphf only
+    pub value_load: f64,         // slots holding a value, live or dead
+    pub tombstone_load: f64,     // slots whose key is reaped
+    pub build: BuildStats,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BuildStats {
+    pub duration: Duration, // mphf build plus placing keys and values
+    pub threads: usize,
+}
+
+impl MapStats {
+    pub(crate) fn new<S, V>(index: &FrozenIndex<S>, store: &Store<V>, build: BuildStats) -> Self
+    where
+        S: KeyStorage,
+        S::Key: Hash + Eq + Clone + Send + Sync + Default,
+        V: Send + Sync + Clone + Default,
+    {
+        let capacity = index.keys.capacity();
+        let mphf_bytes = index.mphf.size_bytes();
+        let per_slot = |n: usize| {
+            if capacity == 0 {
+                0.0
+            } else {
+                n as f64 / capacity as f64
+            }
+        };
+
+        Self {
+            capacity,
+            mphf_bytes,
+            key_bytes: index.keys.key_bytes(),
+            value_bytes: store.value_bytes(),
+            init_bytes: store.init_bytes(),
+            tombstone_bytes: index.keys.tombstone_bytes(),
+            generation_bytes: index.keys.generation_bytes(),
+            bits_per_key: per_slot(mphf_bytes * 8),
+            value_load: per_slot(store.value_count()),
+            tombstone_load: per_slot(index.keys.dead_count()),
+            build,
+        }
+    }
+
+    #[inline]
+    pub fn total_bytes(&self) -> usize {
+        self.mphf_bytes
+            + self.key_bytes
+            + self.value_bytes
+            + self.init_bytes
+            + self.tombstone_bytes
+            + self.generation_bytes
+    }
+}
+
+impl BuildStats {
+    #[inline]
+    pub(crate) fn since(started: Instant, builder: &FrozenMapBuilder) -> Self {
+        Self {
+            duration: started.elapsed(),
+            threads: builder.get_threads(),
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use std::{borrow::Borrow, collections::HashMap, hash::Hash};
+
+use crate::index::prelude::*;
+use crate::map::{BuildError, FrozenMapBuilder, FrozenMapError, UpsertError};
+use crate::store::prelude::*;
+
+// SyncFrozenMap  // striped locks // thread safe through &self // key verification
+//
+// the values sit in one flat slot array guarded by a small array of locks, slot idx under
+// lock idx % stripes, a few locks per core by default, see with_stripes
+//
+// the AtomicIndex tombstone is only flipped under the slot's write lock, so a reap can't race
+// a write to the same key and readers see liveness and value together under the read lock,
+// a VerifiedIndex would need &mut self to reap since its tombstone is a plain bitvec
+//
+// with_value and with_value_mut run their closure under the lock of the slot's stripe, which
+// other keys share, so the closure must not call back into the map, the stripe's RwLock is
+// not reentrant and can deadlock, read with get_cloned first and write after the closure
+
+pub struct SyncFrozenMap<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    index: AtomicIndex<K>,
+    store: StripedStore<V>,
+    reap_policy: ReapPolicy,
+}
+
+impl<K, V> SyncFrozenMap<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    constructors!(V);
+
+    fn from_slots<F>(keys: Vec<K>, value: F, builder: &FrozenMapBuilder) -> Self
+    where
+        F: FnMut(&K) -> Option<V>,
+    {
+        let (index, values) = builder.build_index(keys, value);
+
+        Self {
+            index,
+            store: StripedStore::new(values, StripedStore::<V>::default_stripes()),
+            reap_policy: ReapPolicy::default(),
+        }
+    }
+
+    // number of locks guarding the slots, clamped to 1..=capacity
+    #[inline]
+    pub fn with_stripes(mut self, stripes: usize) -> Self {
+        self.store.set_stripes(stripes);
+        self
+    }
+
+    #[inline] // None for keys outside the key set, liveness is checked under the slot lock
+    fn slot_index<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.index.get_index(key)?;
+        self.index.keys.matches(idx, key).then_some(idx)
+    }
+
+    #[inline]
+    pub fn get_cloned<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.with_value(key, V::clone)
+    }
+
+    #[inline] // f runs under the stripe's read lock and must not touch the map
+    pub fn with_value<Q, F, R>(&self, key: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        F: FnOnce(&V) -> R,
+    {
+        let idx = self.slot_index(key)?;
+
+        self.store.read(idx, |value| match value {
+            Some(value) if !self.index.keys.dead_key(idx) => Some(f(value)),
+            _ => None,
+        })
+    }
+
+    #[inline] // f runs under the stripe's write lock and must not touch the map
+    pub fn with_value_mut<Q, F, R>(&self, key: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        F: FnOnce(&mut V) -> R,
+    {
+        let idx = self.slot_index(key)?;
+
+        self.store.write(idx, |value| match value {
+            Some(value) if !self.index.keys.dead_key(idx) => Some(f(value)),
+            _ => None,
+        })
+    }
+
+    #[inline]
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.slot_index(key)
+            .is_some_and(|idx| !self.index.keys.dead_key(idx))
+    }
+
+    #[inline]
+    pub fn contains_value<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.with_value(key, |_| ()).is_some()
+    }
+
+    #[inline]
+    pub fn upsert(&self, key: K, value: V) -> Result<(), UpsertError<K, V>> {
+        let Some(idx) = self.slot_index(&key) else {
+            let error = FrozenMapError::UnknownKey;
+            return Err(UpsertError { error, key, value });
+        };
+
+        self.store.write(idx, |slot| {
+            if self.index.keys.dead_key(idx) {
+                let error = FrozenMapError::DeadKey;
+                return Err(UpsertError { error, key, value });
+            }
+
+            *slot = Some(value);
+            Ok(())
+        })
+    }
+
+    #[inline]
+    pub fn drop_value<Q>(&self, key: &Q) -> Result<(), FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.slot_index(key).ok_or(FrozenMapError::UnknownKey)?;
+
+        self.store.write(idx, |slot| *slot = None);
+        Ok(())
+    }
+
+    #[inline] // the value is kept, dropped or returned depending on the reap policy
+    pub fn reap_key<Q>(&self, key: &Q) -> Result<Option<V>, FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.slot_index(key).ok_or(FrozenMapError::UnknownKey)?;
+
+        self.store.write(idx, |slot| {
+            if !self.index.keys.try_kill(idx) {
+                return Err(FrozenMapError::AlreadyDead);
+            }
+
+            Ok(match self.reap_policy {
+                ReapPolicy::KeepValue => None,
+                ReapPolicy::DropValue => {
+                    *slot = None;
+                    None
+                }
+                ReapPolicy::ReturnValue => slot.take(),
+            })
+        })
+    }
+
+    #[inline]
+    pub fn rehydrate_key<Q>(&self, key: &Q) -> Result<(), FrozenMapError>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.slot_index(key).ok_or(FrozenMapError::UnknownKey)?;
+
+        self.store.write(idx, |_| {
+            if !self.index.keys.try_rehydrate(idx) {
+                return Err(FrozenMapError::AlreadyAlive);
+            }
+
+            Ok(())
+        })
+    }
+
+    #[inline] // a snapshot while other threads reap or rehydrate
+    pub fn len(&self) -> usize {
+        self.index.keys.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // slots, live and dead
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.index.keys.capacity()
+    }
+
+    #[inline] // number of locks guarding the slots
+    pub fn stripes(&self) -> usize {
+        self.store.stripes()
+    }
+
+    #[inline]
+    pub fn reap_policy(&self) -> ReapPolicy {
+        self.reap_policy
+    }
+
+    #[inline]
+    pub fn set_reap_policy(&mut self, reap_policy: ReapPolicy) {
+        self.reap_policy = reap_policy;
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for SyncFrozenMap<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self::from_iter_last_wins(iter)
+    }
+}
+
+impl<K, V, S> From<HashMap<K, V, S>> for SyncFrozenMap<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    fn from(map: HashMap<K, V, S>) -> Self {
+        Self::from_unique_pairs(map)
+    }
+}
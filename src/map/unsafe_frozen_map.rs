@@ -1,19 +1,13 @@
-use ph::{
-    BuildDefaultSeededHasher,
-    phast::{
-        DefaultCompressedArray, Function2, Params, ShiftOnlyWrapped,
-        bits_per_seed_to_100_bucket_size,
-    },
-    seeds::BitsFast,
-};
-use std::{hash::Hash, mem::MaybeUninit};
-
-use bitvec::bitvec;
+use std::{borrow::Borrow, collections::HashMap, hash::Hash, time::Instant};
 
 use crate::index::prelude::*;
+use crate::map::frozen_map::PREFETCH_BATCH;
+use crate::map::{
+    BuildError, BuildStats, FrozenMapBuilder, FrozenMapError, MapStats, UpsertError, UpsertReport,
+};
 use crate::store::prelude::*;
 
-// SyncUnverifiedFrozenMap  // lowest overhead //not thread safe // no key verification
+// UnsafeFrozenMap  // lowest overhead // not thread safe // no key verification
 
 pub struct UnsafeFrozenMap<K, V>
 where
@@ -22,6 +16,8 @@ where
 {
     index: UnverifiedIndex<K>,
     store: Store<V>,
+    reap_policy: ReapPolicy,
+    build: BuildStats,
 }
 
 impl<K, V> UnsafeFrozenMap<K, V>
@@ -29,101 +25,46 @@ where
     K: Hash + Eq + Send + Sync + Clone + Default,
     V: Send + Sync + Clone + Default,
 {
+    constructors!(V);
+    mutations!(V);
 
-    #[inline]
-    pub fn unsafe_init(keys: Vec<K>, values: Vec<V>) -> Self { // only use if the key value pair indexes line up properly
-        let index_map: Function2<
-            BitsFast,
-            ShiftOnlyWrapped<2>,
-            DefaultCompressedArray,
-            BuildDefaultSeededHasher,
-        > = Function2::with_slice_p_threads_hash_sc(
-            &keys,
-            &Params::new(BitsFast(10), bits_per_seed_to_100_bucket_size(8)),
-            std::thread::available_parallelism().map_or(1, |v| v.into()),
-            BuildDefaultSeededHasher::default(),
-            ShiftOnlyWrapped::<2>,
-        );
-
-        //let mut sorted_keys = vec![K::default(); keys.len()];
-        // note this is expensive to double allocate keys for no good reason aka allocating a default just know the type then we overwrite it which is slow
-
-        // no need to build key vector
-
-        // build values vector
-        let mut sorted_values: Vec<MaybeUninit<V>> = Vec::with_capacity(keys.len()); // allocated memory for n elemens
-        unsafe {
-            sorted_values.set_len(keys.len());
-        } // changes the actual length of the vec to n length without any overhead
-
-        let init_bloom = bitvec![1; keys.len()];
-
-        keys.into_iter().zip(values.into_iter()).for_each(|(key, val)| {
-            let idx = index_map.get(&key);
-
-            //sorted_keys[idx].write(key);
-            sorted_values[idx].write(val);
-        });
-
-        let frozen_index = UnverifiedIndex {
-            mphf: index_map,
-            keys: NoKeys::new(sorted_values.len()),
-        };
-
-        let store = Store::new(sorted_values, init_bloom);
+    fn from_slots<F>(keys: Vec<K>, value: F, builder: &FrozenMapBuilder) -> Self
+    where
+        F: FnMut(&K) -> Option<V>,
+    {
+        let started = Instant::now();
+        let (index, values) = builder.build_index(keys, value);
 
         Self {
-            index: frozen_index,
-            store,
+            index,
+            store: Store::from_slots(values),
+            reap_policy: ReapPolicy::default(),
+            build: BuildStats::since(started, builder),
         }
     }
 
-    #[inline]
-    pub fn from_vec(keys: Vec<K>) -> Self {
-        let index_map: Function2<
-            BitsFast,
-            ShiftOnlyWrapped<2>,
-            DefaultCompressedArray,
-            BuildDefaultSeededHasher,
-        > = Function2::with_slice_p_threads_hash_sc(
-            &keys,
-            &Params::new(BitsFast(10), bits_per_seed_to_100_bucket_size(8)),
-            std::thread::available_parallelism().map_or(1, |v| v.into()),
-            BuildDefaultSeededHasher::default(),
-            ShiftOnlyWrapped::<2>,
-        );
-
-        //let mut sorted_keys = vec![K::default(); keys.len()];
-        // note this is expensive to double allocate keys for no good reason aka allocating a default just know the type then we overwrite it which is slow
-
-        // No need to Build keys vector
-
-        // build values vector
-        let mut sorted_values: Vec<MaybeUninit<V>> = Vec::with_capacity(keys.len()); // allocated memory for n elemens
-        unsafe {
-            sorted_values.set_len(keys.len());
-        } // changes the actual length of the vec to n length without any overhead
-
-        let init_bloom = bitvec![0; keys.len()];
-
-        // No need to populate either keys or values
-
-        let frozen_index = UnverifiedIndex {
-            mphf: index_map,
-            keys: NoKeys::new(keys.len()),
-        };
-
-        let store = Store::new(sorted_values, init_bloom);
+    #[inline] // pairs keys and values by position, the lengths must match
+    pub fn try_init(keys: Vec<K>, values: Vec<V>) -> Result<Self, BuildError<K>> {
+        Self::try_init_with(keys, values, &FrozenMapBuilder::default())
+    }
 
-        Self {
-            index: frozen_index,
-            store,
-        }
+    #[inline]
+    pub fn try_init_with(
+        keys: Vec<K>,
+        values: Vec<V>,
+        builder: &FrozenMapBuilder,
+    ) -> Result<Self, BuildError<K>> {
+        builder.check_lengths(keys.len(), values.len())?;
+        Self::try_from_pairs_with(keys.into_iter().zip(values).collect(), builder)
     }
 
     #[inline]
-    pub fn get(&self, key: &K) -> Option<&V> {
-        let idx = self.index.get_index(key);
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.index.get_index(key)?;
 
         if self.index.keys.dead_key(idx) {
             return None;
@@ -132,48 +73,75 @@ where
         self.store.get_value(idx)
     }
 
-    #[inline]
-    pub fn upsert(&mut self, key: K, value: V) {
-        let idx = self.index.get_index(&key);
-
-        if !self.index.keys.dead_key(idx) {
-            self.store.update(idx, value);
+    // batch get, the value slots of a chunk are prefetched before any of them is read
+    pub fn get_many<Q>(&self, keys: &[Q]) -> Vec<Option<&V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq,
+    {
+        let mut out = Vec::with_capacity(keys.len());
+        let mut idxs = [0usize; PREFETCH_BATCH];
+
+        for chunk in keys.chunks(PREFETCH_BATCH) {
+            for (slot, key) in idxs.iter_mut().zip(chunk) {
+                let Some(idx) = self.index.get_index(key) else {
+                    return keys.iter().map(|_| None).collect(); // empty map
+                };
+
+                self.store.prefetch(idx);
+                *slot = idx;
+            }
+
+            out.extend(idxs.iter().take(chunk.len()).map(|&idx| {
+                if self.index.keys.dead_key(idx) {
+                    return None;
+                }
+
+                self.store.get_value(idx)
+            }));
         }
-    }
 
-    #[inline]
-    pub fn drop_value(&mut self, key: &K) {
-        let idx = self.index.get_index(key);
-
-        self.store.remove_value(idx);
+        out
     }
 
-    #[inline]
-    pub fn reap_key(&mut self, key: &K) -> Result<(), &str> {
-        let idx = self.index.get_index(key);
+    #[inline] // None when the key is dead or holds no value
+    pub fn update_with<Q, F, R>(&mut self, key: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        F: FnOnce(&mut V) -> R,
+    {
+        let idx = self.index.get_index(key)?;
 
         if self.index.keys.dead_key(idx) {
-            return Err("Key is already dead");
+            return None;
         }
 
-        self.index.keys.kill(idx);
-        Ok(())
+        self.store.get_mut_value(idx).map(f)
     }
 
-    #[inline]
-    pub fn rehydrate_key(&mut self, key: &K) -> Result<(), &str> {
-        let idx = self.index.get_index(key);
-
-        if !self.index.keys.dead_key(idx) {
-            return Err("Key is already alive");
-        }
+    #[inline] // memory per part and how the map was built
+    pub fn stats(&self) -> MapStats {
+        MapStats::new(&self.index, &self.store, self.build)
+    }
+}
 
-        self.index.keys.rehydrate(idx);
-        Ok(())
+impl<K, V> FromIterator<(K, V)> for UnsafeFrozenMap<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self::from_iter_last_wins(iter)
     }
+}
 
-    #[inline]
-    pub fn len(&self) -> usize {
-        self.index.keys.len()
+impl<K, V, S> From<HashMap<K, V, S>> for UnsafeFrozenMap<K, V>
+where
+    K: Hash + Eq + Send + Sync + Clone + Default,
+    V: Send + Sync + Clone + Default,
+{
+    fn from(map: HashMap<K, V, S>) -> Self {
+        Self::from_unique_pairs(map)
     }
 }
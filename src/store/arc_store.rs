@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use arc_swap::ArcSwapOption;
+
+// Swappable Arc value slots for ArcFrozenMap, an empty slot holds None, readers get their own
+// Arc so a replaced value is dropped once the last reader lets go of it
+
+pub struct ArcStore<V>
+where
+    V: Send + Sync,
+{
+    values: Box<[ArcSwapOption<V>]>,
+}
+
+impl<V> ArcStore<V>
+where
+    V: Send + Sync,
+{
+    #[inline]
+    pub fn new(len: usize) -> Self {
+        Self {
+            values: (0..len).map(|_| ArcSwapOption::empty()).collect(),
+        }
+    }
+
+    // value slots in slot order, None leaves the slot empty
+    #[inline]
+    pub fn from_slots(values: Vec<Option<V>>) -> Self {
+        Self {
+            values: values
+                .into_iter()
+                .map(|value| ArcSwapOption::new(value.map(Arc::new)))
+                .collect(),
+        }
+    }
+
+    #[inline]
+    pub fn load(&self, idx: usize) -> Option<Arc<V>> {
+        self.values[idx].load_full()
+    }
+
+    #[inline]
+    pub fn is_set(&self, idx: usize) -> bool {
+        self.values[idx].load().is_some()
+    }
+
+    #[inline]
+    pub fn store(&self, idx: usize, value: Option<Arc<V>>) {
+        self.values[idx].store(value);
+    }
+
+    #[inline]
+    pub fn swap(&self, idx: usize, value: Option<Arc<V>>) -> Option<Arc<V>> {
+        self.values[idx].swap(value)
+    }
+
+    // f may run more than once when other writers race on the slot, returns the previous value
+    #[inline]
+    pub fn rcu<F>(&self, idx: usize, mut f: F) -> Option<Arc<V>>
+    where
+        F: FnMut(Option<&V>) -> V,
+    {
+        self.values[idx].rcu(|current| Some(Arc::new(f(current.as_deref()))))
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
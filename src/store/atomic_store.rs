@@ -0,0 +1,146 @@
+use std::sync::atomic::{
+    AtomicBool, AtomicI8, AtomicI16, AtomicI32, AtomicI64, AtomicIsize, AtomicU8, AtomicU16,
+    AtomicU32, AtomicU64, AtomicUsize, Ordering,
+};
+
+// Atomic value slots for AtomicFrozenMap, every slot holds a value from the start
+// (Value::default() unless the constructor wrote one) so there is no init bitset
+
+pub trait AtomicValue: Send + Sync {
+    type Value: Copy + Default;
+
+    fn new(value: Self::Value) -> Self;
+    fn load(&self, order: Ordering) -> Self::Value;
+    fn store(&self, value: Self::Value, order: Ordering);
+    fn swap(&self, value: Self::Value, order: Ordering) -> Self::Value;
+    fn compare_exchange(
+        &self,
+        current: Self::Value,
+        new: Self::Value,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Self::Value, Self::Value>;
+}
+
+// integer atomics, wrapping on overflow like the std ones
+pub trait AtomicInteger: AtomicValue {
+    fn fetch_add(&self, value: Self::Value, order: Ordering) -> Self::Value;
+    fn fetch_sub(&self, value: Self::Value, order: Ordering) -> Self::Value;
+}
+
+macro_rules! atomic_value {
+    ($($atomic:ty => $value:ty),* $(,)?) => {$(
+        impl AtomicValue for $atomic {
+            type Value = $value;
+
+            #[inline(always)]
+            fn new(value: $value) -> Self {
+                <$atomic>::new(value)
+            }
+
+            #[inline(always)]
+            fn load(&self, order: Ordering) -> $value {
+                <$atomic>::load(self, order)
+            }
+
+            #[inline(always)]
+            fn store(&self, value: $value, order: Ordering) {
+                <$atomic>::store(self, value, order)
+            }
+
+            #[inline(always)]
+            fn swap(&self, value: $value, order: Ordering) -> $value {
+                <$atomic>::swap(self, value, order)
+            }
+
+            #[inline(always)]
+            fn compare_exchange(
+                &self,
+                current: $value,
+                new: $value,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$value, $value> {
+                <$atomic>::compare_exchange(self, current, new, success, failure)
+            }
+        }
+    )*};
+}
+
+macro_rules! atomic_integer {
+    ($($atomic:ty => $value:ty),* $(,)?) => {
+        atomic_value!($($atomic => $value),*);
+
+        $(
+            impl AtomicInteger for $atomic {
+                #[inline(always)]
+                fn fetch_add(&self, value: $value, order: Ordering) -> $value {
+                    <$atomic>::fetch_add(self, value, order)
+                }
+
+                #[inline(always)]
+                fn fetch_sub(&self, value: $value, order: Ordering) -> $value {
+                    <$atomic>::fetch_sub(self, value, order)
+                }
+            }
+        )*
+    };
+}
+
+atomic_value!(AtomicBool => bool);
+
+atomic_integer!(
+    AtomicU8 => u8,
+    AtomicU16 => u16,
+    AtomicU32 => u32,
+    AtomicU64 => u64,
+    AtomicUsize => usize,
+    AtomicI8 => i8,
+    AtomicI16 => i16,
+    AtomicI32 => i32,
+    AtomicI64 => i64,
+    AtomicIsize => isize,
+);
+
+pub struct AtomicStore<A>
+where
+    A: AtomicValue,
+{
+    values: Box<[A]>,
+}
+
+impl<A> AtomicStore<A>
+where
+    A: AtomicValue,
+{
+    #[inline]
+    pub fn new(len: usize) -> Self {
+        Self::from_slots((0..len).map(|_| None).collect())
+    }
+
+    // value slots in slot order, None starts at A::Value::default()
+    #[inline]
+    pub fn from_slots(values: Vec<Option<A::Value>>) -> Self {
+        Self {
+            values: values
+                .into_iter()
+                .map(|value| A::new(value.unwrap_or_default()))
+                .collect(),
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, idx: usize) -> &A {
+        &self.values[idx]
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
@@ -1,7 +1,17 @@
+pub mod arc_store;
+pub mod atomic_store;
+#[allow(clippy::module_inception)]
 pub mod store;
+pub mod striped_store;
+pub use arc_store::*;
+pub use atomic_store::*;
 pub use store::*;
+pub use striped_store::*;
 
 // Prelude for easy import in maps
 pub mod prelude {
-    pub use crate::store::store::Store;
+    pub use crate::store::arc_store::ArcStore;
+    pub use crate::store::atomic_store::{AtomicInteger, AtomicStore, AtomicValue};
+    pub use crate::store::store::{Drain, IntoValues, ReapPolicy, Store};
+    pub use crate::store::striped_store::StripedStore;
 }
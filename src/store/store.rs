@@ -1,5 +1,30 @@
 use bitvec::vec::BitVec;
-use std::mem::MaybeUninit;
+use std::mem::{self, MaybeUninit};
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::prefetch::prefetch_read;
+
+// what reaping a key does to the value it holds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReapPolicy {
+    #[default]
+    KeepValue, // the dead key keeps its value, rehydrating it brings the value back
+    DropValue,   // the value is dropped with the key
+    ReturnValue, // the value is moved out to the caller
+}
+
+impl ReapPolicy {
+    // for reaps that have no caller to hand a value to (entries, bulk reaps), the value is kept
+    #[inline]
+    pub fn without_return(self) -> Self {
+        match self {
+            ReapPolicy::ReturnValue => ReapPolicy::KeepValue,
+            policy => policy,
+        }
+    }
+}
 
 pub struct Store<V>
 where
@@ -7,6 +32,8 @@ where
 {
     values: ValueStruct<V>,
     init: BitVec,
+    count: usize,      // initialized slots
+    dead_count: usize, // initialized slots whose key is reaped, see reap / rehydrate
 }
 
 impl<V> Store<V>
@@ -17,20 +44,156 @@ where
     pub fn new(values: Vec<MaybeUninit<V>>, init: BitVec) -> Self {
         Self {
             values: ValueStruct::new(values),
+            count: init.count_ones(),
+            dead_count: 0,
             init,
         }
     }
 
+    // value slots in slot order, None leaves the slot empty
+    pub fn from_slots(values: Vec<Option<V>>) -> Self {
+        let mut init = BitVec::repeat(false, values.len());
+
+        let values = values
+            .into_iter()
+            .enumerate()
+            .map(|(idx, value)| match value {
+                Some(value) => {
+                    init.set(idx, true);
+                    MaybeUninit::new(value)
+                }
+                None => MaybeUninit::uninit(),
+            })
+            .collect();
+
+        Self::new(values, init)
+    }
+
+    // values written to or removed from a slot are counted as live, the map calls
+    // reap / rehydrate whenever the key of a slot changes state and uses the
+    // _dead variants to remove values from reaped slots
+
+    #[inline]
+    pub fn reap(&mut self, idx: usize, policy: ReapPolicy) -> Option<V> {
+        if !self.init[idx] {
+            return None;
+        }
+
+        self.dead_count += 1;
+
+        match policy {
+            ReapPolicy::KeepValue => None,
+            ReapPolicy::DropValue => {
+                self.remove_dead_value(idx);
+                None
+            }
+            ReapPolicy::ReturnValue => self.take_dead(idx),
+        }
+    }
+
+    #[inline]
+    pub fn rehydrate(&mut self, idx: usize) {
+        if self.init[idx] {
+            self.dead_count -= 1;
+        }
+    }
+
+    // every reaped slot came back to life
+    #[inline]
+    pub fn rehydrate_all(&mut self) {
+        self.dead_count = 0;
+    }
+
+    // drops the values still held by the given reaped slots, returns how many were dropped
+    #[inline]
+    pub fn purge_dead<I>(&mut self, dead_slots: I) -> usize
+    where
+        I: Iterator<Item = usize>,
+    {
+        if self.dead_count == 0 {
+            return 0;
+        }
+
+        let before = self.count;
+        dead_slots.for_each(|idx| self.remove_dead_value(idx));
+        before - self.count
+    }
+
+    #[inline]
+    pub fn value_count(&self) -> usize {
+        self.count
+    }
+
+    #[inline]
+    pub fn live_value_count(&self) -> usize {
+        self.count - self.dead_count
+    }
+
+    #[inline]
+    pub fn dead_value_count(&self) -> usize {
+        self.dead_count
+    }
+
+    #[inline] // inline size of every slot, heap memory owned by the values is not followed
+    pub fn value_bytes(&self) -> usize {
+        size_of_val(&*self.values.inner)
+    }
+
+    #[inline]
+    pub fn init_bytes(&self) -> usize {
+        size_of_val(self.init.as_raw_slice())
+    }
+
     #[inline]
     pub fn update(&mut self, idx: usize, value: V) {
+        self.insert(idx, value);
+    }
+
+    #[inline]
+    pub fn insert(&mut self, idx: usize, value: V) -> &mut V {
         if self.init[idx] {
             unsafe {
                 std::ptr::drop_in_place(self.values.inner[idx].as_mut_ptr());
             }
+        } else {
+            self.count += 1;
         }
 
-        self.values.inner[idx].write(value);
         self.init.set(idx, true);
+        self.values.inner[idx].write(value)
+    }
+
+    // writes the value and moves the previous one out instead of dropping it
+    #[inline]
+    pub fn replace(&mut self, idx: usize, value: V) -> Option<V> {
+        if self.init[idx] {
+            Some(mem::replace(
+                unsafe { self.values.inner[idx].assume_init_mut() },
+                value,
+            ))
+        } else {
+            self.insert(idx, value);
+            None
+        }
+    }
+
+    // moves the value out of its slot instead of dropping it
+    #[inline]
+    pub fn take(&mut self, idx: usize) -> Option<V> {
+        if self.init[idx] {
+            self.init.set(idx, false);
+            self.count -= 1;
+            Some(unsafe { self.values.inner[idx].assume_init_read() })
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub fn take_dead(&mut self, idx: usize) -> Option<V> {
+        let value = self.take(idx)?;
+        self.dead_count -= 1;
+        Some(value)
     }
 
     #[inline]
@@ -40,6 +203,15 @@ where
                 std::ptr::drop_in_place(self.values.inner[idx].as_mut_ptr());
             }
             self.init.set(idx, false);
+            self.count -= 1;
+        }
+    }
+
+    #[inline]
+    pub fn remove_dead_value(&mut self, idx: usize) {
+        if self.init[idx] {
+            self.remove_value(idx);
+            self.dead_count -= 1;
         }
     }
 
@@ -63,6 +235,27 @@ where
         }
     }
 
+    // None when an index repeats, is out of bounds or holds no value
+    #[inline]
+    pub fn get_disjoint_mut<const N: usize>(&mut self, idxs: [usize; N]) -> Option<[&mut V; N]> {
+        if idxs
+            .iter()
+            .any(|idx| !self.init.get(*idx).is_some_and(|b| *b))
+        {
+            return None;
+        }
+
+        let slots = self.values.inner.get_disjoint_mut(idxs).ok()?;
+        Some(slots.map(|v| unsafe { v.assume_init_mut() }))
+    }
+
+    // swaps whole slots, an empty slot trades places with a value
+    #[inline]
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.values.inner.swap(a, b);
+        self.init.swap(a, b);
+    }
+
     #[inline]
     pub fn get_values(&self) -> Vec<Option<V>> {
         self.values
@@ -72,6 +265,130 @@ where
             .map(|(i, v)| self.init[i].then(|| unsafe { v.assume_init_ref().clone() }))
             .collect()
     }
+
+    #[inline(always)]
+    pub fn prefetch(&self, idx: usize) {
+        prefetch_read(self.values.inner.as_ptr().wrapping_add(idx));
+    }
+
+    // borrowing walks over the initialized slots only, yielding their slot index
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &V)> {
+        self.init
+            .iter_ones()
+            .map(|i| (i, unsafe { self.values.inner[i].assume_init_ref() }))
+    }
+
+    #[inline]
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (usize, &mut V)> {
+        let init = &self.init;
+
+        self.values
+            .inner
+            .iter_mut()
+            .enumerate()
+            .filter(move |(i, _)| init[*i])
+            .map(|(i, v)| (i, unsafe { v.assume_init_mut() }))
+    }
+
+    #[inline] // drops every value, live and dead
+    pub fn clear(&mut self) {
+        for idx in self.init.iter_ones() {
+            unsafe {
+                self.values.inner[idx].assume_init_drop();
+            }
+        }
+
+        self.init.fill(false);
+        self.count = 0;
+        self.dead_count = 0;
+    }
+
+    #[inline]
+    pub fn drain(&mut self) -> Drain<'_, V> {
+        // the store is emptied before the first value moves, like Vec::drain a leaked
+        // drain leaks the values it still owns but never leaves the counters out of step
+        let empty = BitVec::repeat(false, self.init.len());
+        let init = mem::replace(&mut self.init, empty);
+        let remaining = mem::take(&mut self.count);
+        self.dead_count = 0;
+
+        Drain {
+            values: &mut self.values.inner,
+            init,
+            pos: 0,
+            remaining,
+        }
+    }
+
+    // rayon splits the slots into ranges, each worker reads its part of the init bitvec
+
+    #[cfg(feature = "rayon")]
+    #[inline]
+    pub fn par_iter(&self) -> impl ParallelIterator<Item = (usize, &V)> {
+        (0..self.init.len())
+            .into_par_iter()
+            .filter_map(|idx| Some((idx, self.get_value(idx)?)))
+    }
+
+    #[cfg(feature = "rayon")]
+    #[inline]
+    pub fn par_iter_mut(&mut self) -> impl ParallelIterator<Item = (usize, &mut V)> {
+        let init = &self.init;
+
+        self.values
+            .inner
+            .par_iter_mut()
+            .enumerate()
+            .filter(move |(idx, _)| init[*idx])
+            .map(|(idx, v)| (idx, unsafe { v.assume_init_mut() }))
+    }
+
+    // f runs in parallel, the rejected values are dropped afterwards so a panicking f leaves
+    // every slot as it was
+    #[cfg(feature = "rayon")]
+    pub fn par_retain<F>(&mut self, f: F)
+    where
+        F: Fn(usize, &mut V) -> bool + Sync,
+    {
+        let removed: Vec<usize> = self
+            .par_iter_mut()
+            .filter_map(|(idx, v)| (!f(idx, v)).then_some(idx))
+            .collect();
+
+        removed.into_iter().for_each(|idx| self.remove_value(idx));
+    }
+
+    #[inline]
+    pub fn into_values(mut self) -> IntoValues<V> {
+        IntoValues {
+            values: mem::take(&mut self.values.inner),
+            init: mem::take(&mut self.init),
+            pos: 0,
+        }
+    }
+}
+
+// copies the initialized slots only, the counters carry over
+impl<V> Clone for Store<V>
+where
+    V: Send + Sync + Clone + Default,
+{
+    fn clone(&self) -> Self {
+        let values = (0..self.init.len())
+            .map(|idx| match self.get_value(idx) {
+                Some(value) => MaybeUninit::new(value.clone()),
+                None => MaybeUninit::uninit(),
+            })
+            .collect();
+
+        Self {
+            values: ValueStruct::new(values),
+            init: self.init.clone(),
+            count: self.count,
+            dead_count: self.dead_count,
+        }
+    }
 }
 
 impl<V> Drop for Store<V>
@@ -102,7 +419,94 @@ where
     V: Send + Sync + Clone + Default,
 {
     fn new(values: Vec<MaybeUninit<V>>) -> Self {
-        let inner= values.into_boxed_slice();
+        let inner = values.into_boxed_slice();
         Self { inner }
     }
 }
+
+// moves initialized values out of a consumed store, remaining ones are dropped with the iterator
+pub struct IntoValues<V> {
+    values: Box<[MaybeUninit<V>]>,
+    init: BitVec,
+    pos: usize,
+}
+
+impl<V> Iterator for IntoValues<V> {
+    type Item = (usize, V);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let idx = self.pos + self.init[self.pos..].first_one()?;
+
+        self.init.set(idx, false);
+        self.pos = idx + 1;
+
+        Some((idx, unsafe { self.values[idx].assume_init_read() }))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.init[self.pos..].count_ones();
+        (n, Some(n))
+    }
+}
+
+impl<V> ExactSizeIterator for IntoValues<V> {}
+
+impl<V> Drop for IntoValues<V> {
+    fn drop(&mut self) {
+        for i in self.init.iter_ones() {
+            unsafe {
+                self.values[i].assume_init_drop();
+            }
+        }
+    }
+}
+
+// moves every value out in slot order, values left when the drain is dropped are dropped with it
+pub struct Drain<'a, V>
+where
+    V: Send + Sync + Clone + Default,
+{
+    values: &'a mut [MaybeUninit<V>],
+    init: BitVec, // slots the drain still owns
+    pos: usize,
+    remaining: usize,
+}
+
+impl<V> Iterator for Drain<'_, V>
+where
+    V: Send + Sync + Clone + Default,
+{
+    type Item = V;
+
+    #[inline]
+    fn next(&mut self) -> Option<V> {
+        let idx = self.pos + self.init[self.pos..].first_one()?;
+        self.pos = idx + 1;
+        self.init.set(idx, false);
+        self.remaining -= 1;
+
+        Some(unsafe { self.values[idx].assume_init_read() })
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<V> ExactSizeIterator for Drain<'_, V> where V: Send + Sync + Clone + Default {}
+
+impl<V> Drop for Drain<'_, V>
+where
+    V: Send + Sync + Clone + Default,
+{
+    fn drop(&mut self) {
+        for idx in self.init[self.pos..].iter_ones() {
+            unsafe {
+                self.values[self.pos + idx].assume_init_drop();
+            }
+        }
+    }
+}
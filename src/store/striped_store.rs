@@ -0,0 +1,82 @@
+use std::{
+    cell::UnsafeCell,
+    sync::{PoisonError, RwLock},
+};
+
+// Lock striped value slots for SyncFrozenMap, one flat slot array and a separate array of
+// locks, slot idx is guarded by lock idx % stripes, a closure panicking under a lock does
+// not poison the store
+//
+// read and write hold the stripe lock while f runs, f must not reach the store again since
+// any other slot may sit on the same stripe and the locks are not reentrant
+
+pub struct StripedStore<V> {
+    values: Box<[UnsafeCell<Option<V>>]>,
+    locks: Box<[RwLock<()>]>,
+}
+
+// a slot is only reached through the lock of its stripe
+unsafe impl<V> Sync for StripedStore<V> where V: Send + Sync {}
+
+impl<V> StripedStore<V> {
+    #[inline]
+    pub fn new(values: Vec<Option<V>>, stripes: usize) -> Self {
+        let stripes = stripes.clamp(1, values.len().max(1));
+
+        Self {
+            values: values.into_iter().map(UnsafeCell::new).collect(),
+            locks: (0..stripes).map(|_| RwLock::new(())).collect(),
+        }
+    }
+
+    // a few locks per core keeps contention low without a lock per slot
+    #[inline]
+    pub fn default_stripes() -> usize {
+        std::thread::available_parallelism().map_or(1, usize::from) * 4
+    }
+
+    #[inline]
+    pub fn read<R, F>(&self, idx: usize, f: F) -> R
+    where
+        F: FnOnce(&Option<V>) -> R,
+    {
+        let _guard = self.locks[idx % self.locks.len()]
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        f(unsafe { &*self.values[idx].get() })
+    }
+
+    #[inline]
+    pub fn write<R, F>(&self, idx: usize, f: F) -> R
+    where
+        F: FnOnce(&mut Option<V>) -> R,
+    {
+        let _guard = self.locks[idx % self.locks.len()]
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        f(unsafe { &mut *self.values[idx].get() })
+    }
+
+    #[inline]
+    pub fn stripes(&self) -> usize {
+        self.locks.len()
+    }
+
+    #[inline] // &mut self, no slot is borrowed while the locks are swapped
+    pub fn set_stripes(&mut self, stripes: usize) {
+        let stripes = stripes.clamp(1, self.values.len().max(1));
+        self.locks = (0..stripes).map(|_| RwLock::new(())).collect();
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}